@@ -0,0 +1,139 @@
+use crate::{Action, BUFFER_ROWS, CellType, Game, ROWS};
+use std::time::Duration;
+
+const AGGREGATE_HEIGHT_WEIGHT: f64 = -0.510066;
+const LINES_CLEARED_WEIGHT: f64 = 0.760666;
+const HOLES_WEIGHT: f64 = -0.35663;
+const BUMPINESS_WEIGHT: f64 = -0.184483;
+
+const MOVE_DELAY: Duration = Duration::from_millis(80);
+const MAX_MOVES: u32 = 12;
+
+#[derive(Default)]
+pub struct Demo {
+    piece: u32,
+    target: Option<(usize, i32)>,
+    moves: u32,
+    next_move: Duration,
+}
+
+impl Demo {
+    pub fn next_action(&mut self, game: &Game) -> Option<Action> {
+        if game.game_over || game.paused || game.clock < self.next_move {
+            return None;
+        }
+        if self.target.is_none() || self.piece != game.stats.pieces {
+            self.piece = game.stats.pieces;
+            self.target = best_placement(game);
+            self.moves = 0;
+        }
+        self.next_move = game.clock + MOVE_DELAY;
+        self.moves += 1;
+
+        Some(match self.target {
+            Some((rotation, _)) if self.moves <= MAX_MOVES && game.current.rotation != rotation => {
+                Action::Rotate
+            }
+            Some((_, x)) if self.moves <= MAX_MOVES && game.current_x < x => Action::Right,
+            Some((_, x)) if self.moves <= MAX_MOVES && game.current_x > x => Action::Left,
+            _ => Action::HardDrop,
+        })
+    }
+}
+
+fn filled(game: &Game) -> Vec<Vec<bool>> {
+    game.board
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|cell| {
+                    matches!(
+                        cell,
+                        Some(CellType::Normal(..) | CellType::Garbage | CellType::Obstacle)
+                    )
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn best_placement(game: &Game) -> Option<(usize, i32)> {
+    let board = filled(game);
+    let width = game.width() as i32;
+    let blocked = |x: i32, y: i32| {
+        x < 0 || x >= width || y < 0 || y >= ROWS as i32 || board[y as usize][x as usize]
+    };
+
+    let mut piece = game.current.clone();
+    let mut best: Option<(f64, usize, i32)> = None;
+    for _ in 0..4 {
+        let cells: Vec<(i32, i32)> = piece
+            .shape
+            .iter()
+            .enumerate()
+            .flat_map(|(i, row)| {
+                row.iter()
+                    .enumerate()
+                    .filter(|(_, cell)| **cell)
+                    .map(move |(j, _)| (j as i32, i as i32))
+            })
+            .collect();
+        let fits = |x: i32, y: i32| cells.iter().all(|&(j, i)| !blocked(x + j, y + i));
+
+        for x in -3..width {
+            let mut y = game.current_y;
+            if !fits(x, y) {
+                continue;
+            }
+            while fits(x, y + 1) {
+                y += 1;
+            }
+            let score = evaluate(&board, &cells, x, y);
+            if best.is_none_or(|(top, ..)| score > top) {
+                best = Some((score, piece.rotation, x));
+            }
+        }
+        piece.rotate();
+    }
+    best.map(|(_, rotation, x)| (rotation, x))
+}
+
+fn evaluate(board: &[Vec<bool>], cells: &[(i32, i32)], x: i32, y: i32) -> f64 {
+    let mut board = board.to_vec();
+    for &(j, i) in cells {
+        if y + i < BUFFER_ROWS as i32 {
+            return f64::MIN;
+        }
+        board[(y + i) as usize][(x + j) as usize] = true;
+    }
+    board.retain(|row| !row.iter().all(|&cell| cell));
+    let lines = ROWS - board.len();
+    let width = board.first().map_or(0, Vec::len);
+
+    let heights: Vec<usize> = (0..width)
+        .map(|col| {
+            board
+                .iter()
+                .position(|row| row[col])
+                .map_or(0, |top| board.len() - top)
+        })
+        .collect();
+    let holes: usize = (0..width)
+        .map(|col| {
+            board
+                .iter()
+                .skip_while(|row| !row[col])
+                .filter(|row| !row[col])
+                .count()
+        })
+        .sum();
+    let bumpiness: usize = heights
+        .windows(2)
+        .map(|pair| pair[0].abs_diff(pair[1]))
+        .sum();
+
+    AGGREGATE_HEIGHT_WEIGHT * heights.iter().sum::<usize>() as f64
+        + LINES_CLEARED_WEIGHT * lines as f64
+        + HOLES_WEIGHT * holes as f64
+        + BUMPINESS_WEIGHT * bumpiness as f64
+}
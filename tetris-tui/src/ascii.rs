@@ -0,0 +1,94 @@
+use crate::panel::char_width;
+use std::env;
+
+const LIMITED_TERMS: [&str; 6] = ["dumb", "linux", "vt100", "vt220", "ansi", "cons25"];
+
+pub fn limited_terminal() -> bool {
+    if cfg!(windows) {
+        return false;
+    }
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|value| !value.is_empty())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let utf8 = locale.contains("utf-8") || locale.contains("utf8");
+    let term = env::var("TERM").unwrap_or_default();
+    !utf8 || LIMITED_TERMS.contains(&term.as_str())
+}
+
+pub fn convert(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c.is_ascii() {
+            out.push(c);
+            continue;
+        }
+        match fallback(c) {
+            Some(ascii) => out.push_str(ascii),
+            None => out.extend(std::iter::repeat_n('?', char_width(c))),
+        }
+    }
+    out
+}
+
+fn fallback(c: char) -> Option<&'static str> {
+    Some(match c {
+        '═' => "=",
+        '║' | '│' => "|",
+        '╔' | '╗' | '╚' | '╝' | '╠' | '╣' => "+",
+        '┃' => "!",
+        '╌' => "-",
+        '█' => "#",
+        '▓' => "%",
+        '▒' => "=",
+        '░' => ":",
+        '·' | '…' => ".",
+        '□' => "o",
+        '▰' => "#",
+        '▱' => "-",
+        '▸' | '→' => ">",
+        '◂' | '←' => "<",
+        '↑' => "^",
+        '↓' | '▼' => "v",
+        'à' | 'â' => "a",
+        'À' | 'Â' => "A",
+        'é' | 'è' | 'ê' | 'ë' => "e",
+        'É' | 'È' | 'Ê' | 'Ë' => "E",
+        'î' | 'ï' => "i",
+        'Î' | 'Ï' => "I",
+        'ô' => "o",
+        'Ô' => "O",
+        'ù' | 'û' | 'ü' => "u",
+        'Ù' | 'Û' | 'Ü' => "U",
+        'ç' => "c",
+        'Ç' => "C",
+        'œ' => "oe",
+        'Œ' => "OE",
+        '«' => "<<",
+        '»' => ">>",
+        '💣' => "B ",
+        '⏰' => "S ",
+        '👻' => "G ",
+        '🔨' => "H ",
+        '🎲' => "? ",
+        '📏' => "W ",
+        '🧲' => "M ",
+        '🧀' => "C ",
+        '💀' => "X ",
+        '⏱' => "T ",
+        '⚡' | '🏆' | '✦' => "*",
+        '◆' => "@",
+        '⬛' => ".",
+        '⬜' => "#",
+        '🟥' => "R",
+        '🟩' => "G",
+        '🟨' => "Y",
+        '🟦' => "B",
+        '🟪' => "M",
+        '🟧' => "O",
+        '🟫' => "P",
+        _ => return None,
+    })
+}
@@ -0,0 +1,205 @@
+use std::{
+    io::{self, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+    str::FromStr,
+    sync::{
+        OnceLock,
+        atomic::{AtomicBool, AtomicU8, Ordering},
+        mpsc,
+    },
+    thread,
+    time::Duration,
+};
+
+const SAMPLE_RATE: u32 = 22_050;
+const VOLUME_STEP: u8 = 8;
+pub const MAX_VOLUME: u8 = 5;
+const QUEUED_TONES: usize = 4;
+
+const ROOT_HZ: f64 = 261.63;
+const NATURALS: [(char, i32); 7] = [
+    ('C', 0),
+    ('D', 2),
+    ('E', 4),
+    ('F', 5),
+    ('G', 7),
+    ('A', 9),
+    ('B', 11),
+];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Scale {
+    Major,
+    Minor,
+    Pentatonic,
+    Blues,
+}
+
+impl Scale {
+    fn steps(self) -> &'static [i32] {
+        match self {
+            Scale::Major => &[0, 2, 4, 5, 7, 9, 11],
+            Scale::Minor => &[0, 2, 3, 5, 7, 8, 10],
+            Scale::Pentatonic => &[0, 2, 4, 7, 9],
+            Scale::Blues => &[0, 3, 5, 6, 7, 10],
+        }
+    }
+}
+
+impl FromStr for Scale {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "major" => Ok(Scale::Major),
+            "minor" => Ok(Scale::Minor),
+            "pentatonic" => Ok(Scale::Pentatonic),
+            "blues" => Ok(Scale::Blues),
+            _ => Err(format!("unknown scale: {s}")),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Music {
+    pub key: i32,
+    pub scale: Scale,
+}
+
+impl Default for Music {
+    fn default() -> Self {
+        Music {
+            key: 0,
+            scale: Scale::Major,
+        }
+    }
+}
+
+impl Music {
+    pub fn key(name: &str) -> Option<i32> {
+        let mut chars = name.chars();
+        let letter = chars.next()?.to_ascii_uppercase();
+        let (_, semitone) = NATURALS.iter().find(|(note, _)| *note == letter)?;
+        let shift = match chars.as_str() {
+            "" => 0,
+            "#" => 1,
+            "b" => -1,
+            _ => return None,
+        };
+        Some((semitone + shift).rem_euclid(12))
+    }
+
+    pub fn frequency(self, degree: i32) -> u32 {
+        let steps = self.scale.steps();
+        let len = steps.len() as i32;
+        let semitones =
+            self.key + 12 * degree.div_euclid(len) + steps[degree.rem_euclid(len) as usize];
+        (ROOT_HZ * 2f64.powf(semitones as f64 / 12.0)) as u32
+    }
+}
+
+static MUTED: AtomicBool = AtomicBool::new(false);
+static VOLUME: AtomicU8 = AtomicU8::new(3);
+
+const PLAYERS: [(&str, &[&str]); 2] = [
+    (
+        "aplay",
+        &["-q", "-t", "raw", "-f", "U8", "-r", "22050", "-c", "1", "-"],
+    ),
+    (
+        "paplay",
+        &["--raw", "--format=u8", "--rate=22050", "--channels=1"],
+    ),
+];
+
+struct Player {
+    child: Child,
+    stdin: ChildStdin,
+}
+
+impl Player {
+    fn spawn() -> Option<Player> {
+        PLAYERS.iter().find_map(|(program, args)| {
+            let mut child = Command::new(program)
+                .args(*args)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+                .ok()?;
+            let stdin = child.stdin.take()?;
+            Some(Player { child, stdin })
+        })
+    }
+
+    fn tone(&mut self, frequency: u32, duration_ms: u64) -> io::Result<()> {
+        let samples = (SAMPLE_RATE as u64 * duration_ms / 1000) as u32;
+        let period = (SAMPLE_RATE / frequency.max(1)).max(2);
+        let amplitude = volume() * VOLUME_STEP;
+        let wave: Vec<u8> = (0..samples)
+            .map(|i| {
+                if i % period < period / 2 {
+                    128 + amplitude
+                } else {
+                    128 - amplitude
+                }
+            })
+            .collect();
+        self.stdin.write_all(&wave)
+    }
+}
+
+impl Drop for Player {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+pub fn muted() -> bool {
+    MUTED.load(Ordering::Relaxed)
+}
+
+pub fn toggle_mute() {
+    MUTED.fetch_xor(true, Ordering::Relaxed);
+}
+
+pub fn set_muted(on: bool) {
+    MUTED.store(on, Ordering::Relaxed);
+}
+
+pub fn volume() -> u8 {
+    VOLUME.load(Ordering::Relaxed)
+}
+
+pub fn set_volume(level: u8) {
+    VOLUME.store(level.clamp(1, MAX_VOLUME), Ordering::Relaxed);
+}
+
+pub fn play_sound(frequency: u32, duration_ms: u64) {
+    if muted() {
+        return;
+    }
+    static SOUND: OnceLock<mpsc::SyncSender<(u32, u64)>> = OnceLock::new();
+
+    let sender = SOUND.get_or_init(|| {
+        let (tx, rx) = mpsc::sync_channel::<(u32, u64)>(QUEUED_TONES);
+        thread::spawn(move || {
+            let mut player = Player::spawn();
+            for (frequency, duration_ms) in rx {
+                if let Some(backend) = &mut player
+                    && backend.tone(frequency, duration_ms).is_ok()
+                {
+                    thread::sleep(Duration::from_millis(duration_ms));
+                    continue;
+                }
+                player = None;
+                print!("\x07");
+                let _ = io::stdout().flush();
+                thread::sleep(Duration::from_millis(duration_ms / 10));
+            }
+        });
+        tx
+    });
+    let _ = sender.try_send((frequency, duration_ms));
+}
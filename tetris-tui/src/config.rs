@@ -0,0 +1,959 @@
+use crate::{
+    Action, BLOCK, BombShape, CellType, GameMode, HEIGHT, Layout, PowerUpType, RotationSystem,
+    SlowTimeRule, SpeedCurve, Tetromino, TetrominoType, Theme, WIDTH,
+    audio::{MAX_VOLUME, Music},
+    panel,
+    scores::Pace,
+};
+use crossterm::event::KeyCode;
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Clone)]
+pub struct Scoring {
+    pub lines: [u32; 4],
+    pub combo_percent: u32,
+    pub combo_additive: bool,
+    pub combo_bonus: u32,
+    pub bomb_cell: u32,
+    pub hammer: u32,
+    pub perfect_clear: u32,
+    pub soft_drop: u32,
+    pub hard_drop: u32,
+}
+
+impl Default for Scoring {
+    fn default() -> Self {
+        Scoring {
+            lines: [100, 300, 500, 800],
+            combo_percent: 100,
+            combo_additive: false,
+            combo_bonus: 50,
+            bomb_cell: 10,
+            hammer: 50,
+            perfect_clear: 2000,
+            soft_drop: 0,
+            hard_drop: 0,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Config {
+    pub mode: GameMode,
+    pub fade: Duration,
+    pub layout: Layout,
+    pub rotation: RotationSystem,
+    pub block: String,
+    pub theme: Theme,
+    pub best_score: u32,
+    pub top_score: u32,
+    pub pace: Option<Pace>,
+    pub ascii: Option<bool>,
+    pub practice: bool,
+    pub fall_ms: u64,
+    pub soft_drop_factor: u32,
+    pub start_level: u32,
+    pub goal: Option<u32>,
+    pub level_factor: f64,
+    pub speed_curve: SpeedCurve,
+    pub power_up_chance: f64,
+    pub power_up_every: Duration,
+    pub power_ups_in_stack: bool,
+    pub chaos: bool,
+    pub obstacle_chance: f64,
+    pub obstacle_every: u32,
+    pub safe_rows: usize,
+    pub garbage_clean: bool,
+    pub bag: usize,
+    pub ghost_match_piece: bool,
+    pub ghost_show_hold: bool,
+    pub show_pivot: bool,
+    pub ghost_on_demand: bool,
+    pub ghost_pieces: u32,
+    pub ghost_duration: Duration,
+    pub ghost_solid_lock: bool,
+    pub ghost_keep_color: bool,
+    pub slow_time_rule: SlowTimeRule,
+    pub keep_cancelled_hammer: bool,
+    pub combo_window: Duration,
+    pub combo_cap: u32,
+    pub scoring: Scoring,
+    pub keys: KeyConfig,
+    pub alt_keys: KeyConfig,
+    pub lock_delay: Duration,
+    pub lock_delay_on_drop: bool,
+    pub lock_timeout: Duration,
+    pub lock_show_timer: bool,
+    pub double_tap_drop: Duration,
+    pub move_repeat: Option<Duration>,
+    pub das_carry: bool,
+    pub soft_drop_repeat: Option<Duration>,
+    pub hold_cost: u32,
+    pub hold_cooldown: Duration,
+    pub hold_first_piece: bool,
+    pub hold_next_tick: bool,
+    pub preview_depth: usize,
+    pub no_hold: bool,
+    pub bomb_radius: u64,
+    pub bomb_shape: BombShape,
+    pub bomb_clears_obstacles: bool,
+    pub obstacle_bomb_points: u32,
+    pub obstacle_cleanup_points: u32,
+    pub flashing: bool,
+    pub bullet_time: bool,
+    pub rainbow: bool,
+    pub show_grid: bool,
+    pub column_markers: bool,
+    pub hammer_row_numbers: bool,
+    pub drop_distance: bool,
+    pub age_dim: Duration,
+    pub sound: bool,
+    pub volume: u8,
+    pub music: bool,
+    pub tuning: Music,
+    pub path: Option<PathBuf>,
+    pub serve: Option<(String, u16)>,
+    pub versus: bool,
+    pub mirror: bool,
+    pub demo: bool,
+    pub scenario: Option<usize>,
+    pub start_board: Option<Vec<Vec<Option<CellType>>>>,
+    pub dump_bag: Option<usize>,
+    pub seed: Option<u64>,
+    pub daily: Option<u32>,
+    pub record: Option<PathBuf>,
+    pub keep_replay: bool,
+    pub replay: Option<PathBuf>,
+    pub export: Option<PathBuf>,
+    pub source: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            mode: GameMode::Classic,
+            fade: Duration::from_millis(2000),
+            layout: Layout::Side,
+            rotation: RotationSystem::Simple,
+            block: BLOCK.to_string(),
+            theme: Theme::Classic,
+            best_score: 0,
+            top_score: 0,
+            pace: None,
+            ascii: None,
+            practice: false,
+            fall_ms: 500,
+            soft_drop_factor: 20,
+            start_level: 1,
+            goal: None,
+            level_factor: 0.85,
+            speed_curve: SpeedCurve::Exponential,
+            power_up_chance: 0.4,
+            power_up_every: Duration::ZERO,
+            power_ups_in_stack: false,
+            chaos: false,
+            obstacle_chance: 0.3,
+            obstacle_every: 5,
+            safe_rows: 0,
+            garbage_clean: false,
+            bag: 0,
+            ghost_match_piece: false,
+            ghost_show_hold: false,
+            show_pivot: false,
+            ghost_on_demand: false,
+            ghost_pieces: 3,
+            ghost_duration: Duration::ZERO,
+            ghost_solid_lock: false,
+            ghost_keep_color: false,
+            slow_time_rule: SlowTimeRule::Refresh,
+            keep_cancelled_hammer: true,
+            combo_window: Duration::from_secs(3),
+            combo_cap: 0,
+            scoring: Scoring::default(),
+            keys: KeyConfig::default(),
+            alt_keys: KeyConfig::versus_left(),
+            lock_delay: Duration::from_millis(500),
+            lock_delay_on_drop: false,
+            lock_timeout: Duration::ZERO,
+            lock_show_timer: false,
+            double_tap_drop: Duration::ZERO,
+            move_repeat: Some(Duration::ZERO),
+            das_carry: false,
+            soft_drop_repeat: Some(Duration::ZERO),
+            hold_cost: 0,
+            hold_cooldown: Duration::ZERO,
+            hold_first_piece: true,
+            hold_next_tick: false,
+            preview_depth: 1,
+            no_hold: false,
+            bomb_radius: 2,
+            bomb_shape: BombShape::Square,
+            bomb_clears_obstacles: false,
+            obstacle_bomb_points: 30,
+            obstacle_cleanup_points: 50,
+            flashing: true,
+            bullet_time: false,
+            rainbow: false,
+            show_grid: false,
+            column_markers: false,
+            hammer_row_numbers: false,
+            drop_distance: false,
+            age_dim: Duration::ZERO,
+            sound: true,
+            volume: 3,
+            music: false,
+            tuning: Music::default(),
+            path: None,
+            serve: None,
+            versus: false,
+            mirror: false,
+            demo: false,
+            scenario: None,
+            start_board: None,
+            dump_bag: None,
+            seed: None,
+            daily: None,
+            record: None,
+            keep_replay: false,
+            replay: None,
+            export: None,
+            source: String::new(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct KeyConfig {
+    pub left: Vec<KeyCode>,
+    pub right: Vec<KeyCode>,
+    pub soft_drop: Vec<KeyCode>,
+    pub rotate: Vec<KeyCode>,
+    pub hard_drop: Vec<KeyCode>,
+    pub hold: Vec<KeyCode>,
+    pub peek: Vec<KeyCode>,
+    pub hammer: Vec<KeyCode>,
+}
+
+impl Default for KeyConfig {
+    fn default() -> Self {
+        KeyConfig {
+            left: vec![KeyCode::Left],
+            right: vec![KeyCode::Right],
+            soft_drop: vec![KeyCode::Down],
+            rotate: vec![KeyCode::Up],
+            hard_drop: vec![KeyCode::Char(' ')],
+            hold: vec![KeyCode::Char('c')],
+            peek: vec![KeyCode::Char('x')],
+            hammer: vec![KeyCode::Char('h')],
+        }
+    }
+}
+
+impl KeyConfig {
+    pub fn versus_right() -> Self {
+        KeyConfig {
+            hard_drop: vec![KeyCode::Enter],
+            hold: vec![KeyCode::Char('0')],
+            peek: vec![KeyCode::Char('.')],
+            hammer: vec![KeyCode::Char('+')],
+            ..KeyConfig::default()
+        }
+    }
+
+    pub fn versus_left() -> Self {
+        KeyConfig {
+            left: vec![KeyCode::Char('a')],
+            right: vec![KeyCode::Char('d')],
+            soft_drop: vec![KeyCode::Char('s')],
+            rotate: vec![KeyCode::Char('w')],
+            hard_drop: vec![KeyCode::Char(' ')],
+            hold: vec![KeyCode::Char('c')],
+            peek: vec![KeyCode::Char('x')],
+            hammer: vec![KeyCode::Char('e')],
+        }
+    }
+
+    pub fn peeks(&self, code: KeyCode) -> bool {
+        self.peek.contains(&lowercase(code))
+    }
+
+    pub fn arms_hammer(&self, code: KeyCode) -> bool {
+        self.hammer.contains(&lowercase(code))
+    }
+
+    pub fn action(&self, code: KeyCode) -> Option<Action> {
+        let code = lowercase(code);
+        [
+            (&self.left, Action::Left),
+            (&self.right, Action::Right),
+            (&self.soft_drop, Action::SoftDrop),
+            (&self.rotate, Action::Rotate),
+            (&self.hard_drop, Action::HardDrop),
+            (&self.hold, Action::Hold),
+        ]
+        .into_iter()
+        .find(|(keys, _)| keys.contains(&code))
+        .map(|(_, action)| action)
+    }
+}
+
+fn lowercase(code: KeyCode) -> KeyCode {
+    match code {
+        KeyCode::Char(c) => KeyCode::Char(c.to_ascii_lowercase()),
+        code => code,
+    }
+}
+
+enum Value {
+    Str(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+}
+
+impl Value {
+    fn parse(raw: &str) -> Option<Value> {
+        if let Some(inner) = raw.strip_prefix('"').and_then(|r| r.strip_suffix('"')) {
+            let mut text = String::new();
+            let mut chars = inner.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => match chars.next()? {
+                        'n' => text.push('\n'),
+                        't' => text.push('\t'),
+                        c @ ('"' | '\\') => text.push(c),
+                        _ => return None,
+                    },
+                    '"' => return None,
+                    c => text.push(c),
+                }
+            }
+            return Some(Value::Str(text));
+        }
+
+        match raw {
+            "true" => Some(Value::Bool(true)),
+            "false" => Some(Value::Bool(false)),
+            _ => raw
+                .parse()
+                .map(Value::Int)
+                .or_else(|_| raw.parse().map(Value::Float))
+                .ok(),
+        }
+    }
+
+    fn into_string(self, key: &str) -> Result<String, String> {
+        match self {
+            Value::Str(s) => Ok(s),
+            _ => Err(format!("{key} expects a string")),
+        }
+    }
+
+    fn into_bool(self, key: &str) -> Result<bool, String> {
+        match self {
+            Value::Bool(b) => Ok(b),
+            _ => Err(format!("{key} expects true or false")),
+        }
+    }
+
+    fn into_u64(self, key: &str) -> Result<u64, String> {
+        match self {
+            Value::Int(n) if n >= 0 => Ok(n as u64),
+            _ => Err(format!("{key} expects a positive integer")),
+        }
+    }
+
+    fn into_f64(self, key: &str) -> Result<f64, String> {
+        match self {
+            Value::Int(n) => Ok(n as f64),
+            Value::Float(f) => Ok(f),
+            _ => Err(format!("{key} expects a number")),
+        }
+    }
+}
+
+impl Config {
+    pub fn from_args(args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let args: Vec<String> = args.collect();
+
+        let path = match args.iter().position(|arg| arg == "--config") {
+            Some(i) => Some(PathBuf::from(value("--config", args.get(i + 1).cloned())?)),
+            None => default_path(),
+        };
+        let mut config = match &path {
+            Some(path) if path.exists() || args.iter().any(|arg| arg == "--config") => {
+                Config::load(path)?
+            }
+            _ => Config::default(),
+        };
+        config.path = path;
+        config.apply_args(args)?;
+        Ok(config)
+    }
+
+    pub fn from_replay(args: &[String], source: &str, board: &[String]) -> Result<Self, String> {
+        let mut config = Config::parse("config", source)?;
+        config.apply_args(args.to_vec())?;
+        if !board.is_empty() {
+            config.start_board = Some(parse_board("board", &board.join("\n"))?);
+        }
+        Ok(config)
+    }
+
+    fn apply_args(&mut self, args: Vec<String>) -> Result<(), String> {
+        let config = self;
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--config" => {
+                    args.next();
+                }
+                "--mode" => config.mode = value(&arg, args.next())?.parse()?,
+                "--layout" => config.layout = value(&arg, args.next())?.parse()?,
+                "--rotation" => config.rotation = value(&arg, args.next())?.parse()?,
+                "--block" => config.block = non_empty(&arg, value(&arg, args.next())?)?,
+                "--practice" => config.practice = true,
+                "--ascii" => config.ascii = Some(true),
+                "--utf8" => config.ascii = Some(false),
+                "--versus" => config.versus = true,
+                "--demo" => config.demo = true,
+                "--dump-bag" => {
+                    let count = value(&arg, args.next())?;
+                    config.dump_bag = Some(
+                        count
+                            .parse()
+                            .map_err(|_| format!("invalid count: {count}"))?,
+                    );
+                }
+                "--scenario" => {
+                    let number = value(&arg, args.next())?;
+                    config.scenario = match number.parse() {
+                        Ok(n @ 1..=3) => Some(n),
+                        _ => return Err(format!("--scenario expects 1, 2 or 3, got {number}")),
+                    };
+                    config.practice = true;
+                }
+                "--board" => {
+                    let path = value(&arg, args.next())?;
+                    config.start_board = Some(load_board(Path::new(&path))?);
+                }
+                "--mirror" => config.mirror = true,
+                "--no-hold" => config.no_hold = true,
+                "--no-preview" => config.preview_depth = 0,
+                "--preview" => {
+                    config.preview_depth =
+                        preview_depth(&arg, value(&arg, args.next())?.parse().ok())?
+                }
+                "--level" => {
+                    let level = value(&arg, args.next())?;
+                    config.start_level = match level.parse() {
+                        Ok(n) if n > 0 => n,
+                        _ => return Err(format!("invalid level: {level}")),
+                    };
+                }
+                "--goal" => {
+                    let goal = value(&arg, args.next())?;
+                    config.goal = match goal.parse() {
+                        Ok(n) if n > 0 => Some(n),
+                        _ => return Err(format!("invalid goal: {goal}")),
+                    };
+                }
+                "--bag" => config.bag = bag_size(&arg, value(&arg, args.next())?.parse().ok())?,
+                "--seed" => {
+                    let seed = value(&arg, args.next())?;
+                    config.seed = Some(seed.parse().map_err(|_| format!("invalid seed: {seed}"))?);
+                }
+                "--daily" => {
+                    let date = today();
+                    config.daily = Some(date);
+                    config.seed = Some(date as u64);
+                }
+                "--serve" => {
+                    let address = value(&arg, args.next())?;
+                    let (host, port) = match address.strip_prefix('[') {
+                        Some(rest) => rest
+                            .split_once("]:")
+                            .ok_or_else(|| format!("{arg} expects [addr]:port, got {address}"))?,
+                        None if address.matches(':').count() > 1 => {
+                            return Err(format!(
+                                "{arg}: write IPv6 addresses as [addr]:port, got {address}"
+                            ));
+                        }
+                        None => address.split_once(':').unwrap_or(("127.0.0.1", &address)),
+                    };
+                    let port = port.parse().map_err(|_| format!("invalid port: {port}"))?;
+                    config.serve = Some((host.to_string(), port));
+                }
+                "--record" => config.record = Some(value(&arg, args.next())?.into()),
+                "--replay" => config.replay = Some(value(&arg, args.next())?.into()),
+                "--export" => config.export = Some(value(&arg, args.next())?.into()),
+                _ => return Err(format!("unknown argument: {arg}")),
+            }
+        }
+
+        if config.export.is_some() && config.replay.is_none() {
+            return Err("--export needs --replay".to_string());
+        }
+        if config.start_board.is_some() && config.scenario.is_some() {
+            return Err("--board cannot be combined with --scenario".to_string());
+        }
+        if config.record.is_some() && (config.replay.is_some() || config.demo) {
+            return Err("--record cannot be combined with --replay or --demo".to_string());
+        }
+
+        Ok(())
+    }
+
+    fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|err| format!("{}: {err}", path.display()))?;
+        Config::parse(&path.display().to_string(), &text)
+    }
+
+    fn parse(source: &str, text: &str) -> Result<Self, String> {
+        let mut config = Config {
+            source: text.to_string(),
+            ..Config::default()
+        };
+        let mut section = String::new();
+
+        for (n, line) in text.lines().enumerate() {
+            let at = |msg: String| format!("{source}:{}: {msg}", n + 1);
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = name.trim().to_string();
+                continue;
+            }
+
+            let (key, raw) = line
+                .split_once('=')
+                .ok_or_else(|| at("expected `key = value`".to_string()))?;
+            let key = match section.as_str() {
+                "" => key.trim().to_string(),
+                section => format!("{section}.{}", key.trim()),
+            };
+            let value =
+                Value::parse(raw.trim()).ok_or_else(|| at(format!("invalid value for {key}")))?;
+            config.set(&key, value).map_err(at)?;
+        }
+
+        Ok(config)
+    }
+
+    fn set(&mut self, key: &str, value: Value) -> Result<(), String> {
+        match key {
+            "mode" => self.mode = value.into_string(key)?.parse()?,
+            "invisible.fade_ms" => self.fade = Duration::from_millis(value.into_u64(key)?),
+            "layout" => self.layout = value.into_string(key)?.parse()?,
+            "rotation" => self.rotation = value.into_string(key)?.parse()?,
+            "block" => self.block = non_empty(key, value.into_string(key)?)?,
+            "theme" => self.theme = value.into_string(key)?.parse()?,
+            "practice" => self.practice = value.into_bool(key)?,
+            "ascii" => {
+                self.ascii = match value {
+                    Value::Bool(on) => Some(on),
+                    Value::Str(s) if s == "auto" => None,
+                    _ => return Err(format!("{key} expects true, false or auto")),
+                }
+            }
+            "speed.fall_ms" => self.fall_ms = positive(key, value)?,
+            "speed.soft_drop_factor" => self.soft_drop_factor = positive(key, value)? as u32,
+            "speed.start_level" => self.start_level = positive(key, value)? as u32,
+            "goal" => self.goal = Some(positive(key, value)? as u32),
+            "speed.level_factor" => self.level_factor = chance(key, value)?,
+            "speed.curve" => self.speed_curve = value.into_string(key)?.parse()?,
+            "spawn.power_up_chance" => self.power_up_chance = chance(key, value)?,
+            "spawn.obstacle_chance" => self.obstacle_chance = chance(key, value)?,
+            "spawn.obstacle_every" => self.obstacle_every = positive(key, value)? as u32,
+            "spawn.chaos" => self.chaos = value.into_bool(key)?,
+            "spawn.power_up_in_stack" => self.power_ups_in_stack = value.into_bool(key)?,
+            "spawn.power_up_every_ms" => {
+                self.power_up_every = Duration::from_millis(value.into_u64(key)?)
+            }
+            "spawn.safe_rows" => self.safe_rows = value.into_u64(key)? as usize,
+            "spawn.bag" => self.bag = bag_size(key, value.into_u64(key).ok())?,
+            "power_ups.ghost_pieces" => self.ghost_pieces = positive(key, value)? as u32,
+            "power_ups.ghost_ms" => {
+                self.ghost_duration = Duration::from_millis(value.into_u64(key)?)
+            }
+            "power_ups.ghost_solid_lock" => self.ghost_solid_lock = value.into_bool(key)?,
+            "power_ups.ghost_keep_color" => self.ghost_keep_color = value.into_bool(key)?,
+            "power_ups.cancelled_hammer" => {
+                self.keep_cancelled_hammer = match value.into_string(key)?.as_str() {
+                    "keep" => true,
+                    "forfeit" => false,
+                    other => return Err(format!("{key} expects keep or forfeit, got {other}")),
+                }
+            }
+            "power_ups.slow_time" => self.slow_time_rule = value.into_string(key)?.parse()?,
+            "garbage.clean" => self.garbage_clean = value.into_bool(key)?,
+            "ghost.match_piece" => self.ghost_match_piece = value.into_bool(key)?,
+            "ghost.on_demand" => self.ghost_on_demand = value.into_bool(key)?,
+            "ghost.show_hold" => self.ghost_show_hold = value.into_bool(key)?,
+            "board.pivot" => self.show_pivot = value.into_bool(key)?,
+            "keys.left" => self.keys.left = key_codes(key, value)?,
+            "keys.right" => self.keys.right = key_codes(key, value)?,
+            "keys.soft_drop" => self.keys.soft_drop = key_codes(key, value)?,
+            "keys.rotate" => self.keys.rotate = key_codes(key, value)?,
+            "keys.hard_drop" => self.keys.hard_drop = key_codes(key, value)?,
+            "keys.hold" => self.keys.hold = key_codes(key, value)?,
+            "keys.peek" => self.keys.peek = key_codes(key, value)?,
+            "keys.hammer" => self.keys.hammer = key_codes(key, value)?,
+            "keys_alt.left" => self.alt_keys.left = key_codes(key, value)?,
+            "keys_alt.right" => self.alt_keys.right = key_codes(key, value)?,
+            "keys_alt.soft_drop" => self.alt_keys.soft_drop = key_codes(key, value)?,
+            "keys_alt.rotate" => self.alt_keys.rotate = key_codes(key, value)?,
+            "keys_alt.hard_drop" => self.alt_keys.hard_drop = key_codes(key, value)?,
+            "keys_alt.hold" => self.alt_keys.hold = key_codes(key, value)?,
+            "keys_alt.peek" => self.alt_keys.peek = key_codes(key, value)?,
+            "keys_alt.hammer" => self.alt_keys.hammer = key_codes(key, value)?,
+            "score.single" => self.scoring.lines[0] = value.into_u64(key)? as u32,
+            "score.double" => self.scoring.lines[1] = value.into_u64(key)? as u32,
+            "score.triple" => self.scoring.lines[2] = value.into_u64(key)? as u32,
+            "score.tetris" => self.scoring.lines[3] = value.into_u64(key)? as u32,
+            "score.combo_percent" => self.scoring.combo_percent = value.into_u64(key)? as u32,
+            "score.combo" => {
+                self.scoring.combo_additive = match value.into_string(key)?.as_str() {
+                    "multiply" => false,
+                    "guideline" => true,
+                    other => {
+                        return Err(format!("{key} expects multiply or guideline, got {other}"));
+                    }
+                }
+            }
+            "score.combo_bonus" => self.scoring.combo_bonus = value.into_u64(key)? as u32,
+            "score.bomb_cell" => self.scoring.bomb_cell = value.into_u64(key)? as u32,
+            "score.hammer" => self.scoring.hammer = value.into_u64(key)? as u32,
+            "score.perfect_clear" => self.scoring.perfect_clear = value.into_u64(key)? as u32,
+            "score.soft_drop" => self.scoring.soft_drop = value.into_u64(key)? as u32,
+            "score.hard_drop" => self.scoring.hard_drop = value.into_u64(key)? as u32,
+            "combo.window_ms" => self.combo_window = Duration::from_millis(positive(key, value)?),
+            "combo.max_multiplier" => self.combo_cap = value.into_u64(key)? as u32,
+            "lock.show_timer" => self.lock_show_timer = value.into_bool(key)?,
+            "lock.delay_ms" => self.lock_delay = Duration::from_millis(value.into_u64(key)?),
+            "lock.timeout_ms" => self.lock_timeout = Duration::from_millis(value.into_u64(key)?),
+            "lock.on_hard_drop" => {
+                self.lock_delay_on_drop = match value.into_string(key)?.as_str() {
+                    "instant" => false,
+                    "delay" => true,
+                    other => return Err(format!("{key} expects instant or delay, got {other}")),
+                }
+            }
+            "drop.double_tap_ms" => {
+                self.double_tap_drop = Duration::from_millis(value.into_u64(key)?)
+            }
+            "repeat.move" => self.move_repeat = repeat(key, value)?,
+            "repeat.carry" => self.das_carry = value.into_bool(key)?,
+            "repeat.soft_drop" => self.soft_drop_repeat = repeat(key, value)?,
+            "hold.enabled" => self.no_hold = !value.into_bool(key)?,
+            "hold.cost" => self.hold_cost = value.into_u64(key)? as u32,
+            "hold.cooldown_ms" => self.hold_cooldown = Duration::from_millis(value.into_u64(key)?),
+            "hold.first_piece" => self.hold_first_piece = value.into_bool(key)?,
+            "hold.spawn" => {
+                self.hold_next_tick = match value.into_string(key)?.as_str() {
+                    "immediate" => false,
+                    "next_tick" => true,
+                    other => {
+                        return Err(format!("{key} expects immediate or next_tick, got {other}"));
+                    }
+                }
+            }
+            "preview.depth" => self.preview_depth = preview_depth(key, value.into_u64(key).ok())?,
+            "bomb.radius" => self.bomb_radius = positive(key, value)?,
+            "bomb.shape" => self.bomb_shape = value.into_string(key)?.parse()?,
+            "bomb.clear_obstacles" => self.bomb_clears_obstacles = value.into_bool(key)?,
+            "obstacles.bomb_points" => self.obstacle_bomb_points = value.into_u64(key)? as u32,
+            "obstacles.cleanup_points" => {
+                self.obstacle_cleanup_points = value.into_u64(key)? as u32
+            }
+            "effects.flashing" => self.flashing = value.into_bool(key)?,
+            "effects.bullet_time" => self.bullet_time = value.into_bool(key)?,
+            "effects.rainbow" => self.rainbow = value.into_bool(key)?,
+            "board.column_markers" => self.column_markers = value.into_bool(key)?,
+            "board.hammer_rows" => self.hammer_row_numbers = value.into_bool(key)?,
+            "board.drop_distance" => self.drop_distance = value.into_bool(key)?,
+            "board.mirror" => self.mirror = value.into_bool(key)?,
+            "board.grid" => self.show_grid = value.into_bool(key)?,
+            "board.age_ms" => self.age_dim = Duration::from_millis(value.into_u64(key)?),
+            "replay.keep_last" => self.keep_replay = value.into_bool(key)?,
+            "sound.enabled" => self.sound = value.into_bool(key)?,
+            "sound.music" => self.music = value.into_bool(key)?,
+            "sound.key" => {
+                let name = value.into_string(key)?;
+                self.tuning.key = Music::key(&name)
+                    .ok_or_else(|| format!("{key} expects a note like C, F# or Bb, got {name}"))?;
+            }
+            "sound.scale" => self.tuning.scale = value.into_string(key)?.parse()?,
+            "sound.volume" => {
+                self.volume = match value.into_u64(key)? {
+                    n @ 1.. if n <= MAX_VOLUME as u64 => n as u8,
+                    _ => return Err(format!("{key} expects a number between 1 and {MAX_VOLUME}")),
+                }
+            }
+            _ => return Err(format!("unknown setting: {key}")),
+        }
+        Ok(())
+    }
+
+    pub fn cell_width(&self) -> usize {
+        self.block.chars().map(panel::char_width).sum()
+    }
+}
+
+pub fn save(path: &Path, values: &[(&str, String)]) -> io::Result<()> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err),
+    };
+    let mut lines: Vec<String> = text.lines().map(str::to_string).collect();
+    for (key, value) in values {
+        let (section, name) = key.split_once('.').unwrap_or(("", key));
+        set_line(&mut lines, section, name, value);
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, lines.join("\n") + "\n")
+}
+
+fn set_line(lines: &mut Vec<String>, section: &str, name: &str, value: &str) {
+    let mut current = String::new();
+    let mut insert_at = section.is_empty().then_some(0);
+    for (i, line) in lines.iter_mut().enumerate() {
+        let code = strip_comment(line);
+        let trimmed = code.trim();
+        if let Some(header) = trimmed.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            current = header.trim().to_string();
+            if current == section {
+                insert_at = Some(i + 1);
+            }
+            continue;
+        }
+        if current != section || trimmed.is_empty() {
+            continue;
+        }
+        insert_at = Some(i + 1);
+        if let Some((key, raw)) = code.split_once('=')
+            && key.trim() == name
+        {
+            let gap = &raw[raw.trim_end().len()..];
+            let comment = line[code.len()..].to_string();
+            *line = format!("{key}= {value}{gap}{comment}");
+            return;
+        }
+    }
+    match insert_at {
+        Some(i) => lines.insert(i, format!("{name} = {value}")),
+        None => {
+            if lines.last().is_some_and(|line| !line.trim().is_empty()) {
+                lines.push(String::new());
+            }
+            lines.push(format!("[{section}]"));
+            lines.push(format!("{name} = {value}"));
+        }
+    }
+}
+
+fn default_path() -> Option<PathBuf> {
+    let dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(dir.join("tetris-tui").join("config.toml"))
+}
+
+fn today() -> u32 {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let days = (secs / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let doe = days - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year * 10_000 + month * 100 + day) as u32
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut chars = line.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' if in_string => {
+                chars.next();
+            }
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn positive(key: &str, value: Value) -> Result<u64, String> {
+    match value.into_u64(key)? {
+        0 => Err(format!("{key} must be greater than 0")),
+        n => Ok(n),
+    }
+}
+
+fn bag_size(key: &str, size: Option<u64>) -> Result<usize, String> {
+    match size {
+        Some(n) if n % 7 == 0 => Ok(n as usize),
+        _ => Err(format!("{key} expects 0 (pure random) or a multiple of 7")),
+    }
+}
+
+fn load_board(path: &Path) -> Result<Vec<Vec<Option<CellType>>>, String> {
+    let text = fs::read_to_string(path).map_err(|err| format!("{}: {err}", path.display()))?;
+    parse_board(&path.display().to_string(), &text)
+}
+
+pub fn parse_board(source: &str, text: &str) -> Result<Vec<Vec<Option<CellType>>>, String> {
+    let mut rows = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim_end();
+        if line.is_empty() {
+            continue;
+        }
+        let at = |msg: String| format!("{source}:{}: {msg}", n + 1);
+        let row = line
+            .chars()
+            .map(|c| board_cell(c).ok_or_else(|| at(format!("invalid cell '{c}'"))))
+            .collect::<Result<Vec<_>, _>>()?;
+        if row.len() != WIDTH {
+            return Err(at(format!("expected {WIDTH} cells, got {}", row.len())));
+        }
+        rows.push(row);
+    }
+    if rows.is_empty() || rows.len() > HEIGHT {
+        return Err(format!(
+            "{source}: expected 1 to {HEIGHT} rows, got {}",
+            rows.len()
+        ));
+    }
+    Ok(rows)
+}
+
+pub fn board_char(cell: Option<CellType>) -> char {
+    match cell {
+        None => '.',
+        Some(CellType::Garbage) => '#',
+        Some(CellType::Obstacle) => 'X',
+        Some(CellType::PowerUp(powerup)) => match powerup {
+            PowerUpType::Bomb => 'b',
+            PowerUpType::SlowTime => 's',
+            PowerUpType::Ghost => 'g',
+            PowerUpType::Hammer => 'h',
+            PowerUpType::Random => 'r',
+            PowerUpType::Well => 'w',
+            PowerUpType::Tilt => 'm',
+        },
+        Some(CellType::Normal(color, _)) => TetrominoType::ALL
+            .into_iter()
+            .find(|&typ| Tetromino::new(typ).color == color)
+            .map_or('#', TetrominoType::name),
+    }
+}
+
+fn board_cell(c: char) -> Option<Option<CellType>> {
+    let powerup = match c {
+        '.' => return Some(None),
+        '#' => return Some(Some(CellType::Garbage)),
+        'X' => return Some(Some(CellType::Obstacle)),
+        'b' => PowerUpType::Bomb,
+        's' => PowerUpType::SlowTime,
+        'g' => PowerUpType::Ghost,
+        'h' => PowerUpType::Hammer,
+        'r' => PowerUpType::Random,
+        'w' => PowerUpType::Well,
+        'm' => PowerUpType::Tilt,
+        _ => {
+            let typ = TetrominoType::ALL.into_iter().find(|typ| typ.name() == c)?;
+            return Some(Some(CellType::Normal(
+                Tetromino::new(typ).color,
+                Duration::ZERO,
+            )));
+        }
+    };
+    Some(Some(CellType::PowerUp(powerup)))
+}
+
+fn repeat(key: &str, value: Value) -> Result<Option<Duration>, String> {
+    match value {
+        Value::Bool(true) => Ok(Some(Duration::ZERO)),
+        Value::Bool(false) => Ok(None),
+        Value::Int(n) if n >= 0 => Ok(Some(Duration::from_millis(n as u64))),
+        _ => Err(format!(
+            "{key} expects true, false or a delay in milliseconds"
+        )),
+    }
+}
+
+fn preview_depth(key: &str, depth: Option<u64>) -> Result<usize, String> {
+    match depth {
+        Some(n @ 0..=6) => Ok(n as usize),
+        _ => Err(format!("{key} expects a number between 0 and 6")),
+    }
+}
+
+fn chance(key: &str, value: Value) -> Result<f64, String> {
+    let p = value.into_f64(key)?;
+    if (0.0..=1.0).contains(&p) {
+        Ok(p)
+    } else {
+        Err(format!("{key} must be between 0 and 1"))
+    }
+}
+
+const RESERVED_KEYS: &str = "pgrqtv123456789";
+
+fn key_codes(key: &str, value: Value) -> Result<Vec<KeyCode>, String> {
+    value
+        .into_string(key)?
+        .split_whitespace()
+        .map(|name| key_code(key, name))
+        .collect()
+}
+
+fn key_code(key: &str, name: &str) -> Result<KeyCode, String> {
+    let mut chars = name.chars();
+    match (name, chars.next(), chars.next()) {
+        ("left", ..) => Ok(KeyCode::Left),
+        ("right", ..) => Ok(KeyCode::Right),
+        ("up", ..) => Ok(KeyCode::Up),
+        ("down", ..) => Ok(KeyCode::Down),
+        ("enter", ..) => Ok(KeyCode::Enter),
+        ("space", ..) => Ok(KeyCode::Char(' ')),
+        (_, Some(c), None) if RESERVED_KEYS.contains(c.to_ascii_lowercase()) => Err(format!(
+            "{key} can't use {name}, the game keeps P, G, R, Q, T, V and 1-9 for itself"
+        )),
+        (_, Some(c), None) if c.is_ascii_graphic() => Ok(KeyCode::Char(c.to_ascii_lowercase())),
+        _ => Err(format!(
+            "{key} expects single characters, left, right, up, down, enter or space, separated by spaces, got {name}"
+        )),
+    }
+}
+
+fn non_empty(key: &str, text: String) -> Result<String, String> {
+    if text.is_empty() {
+        Err(format!("{key} expects at least one character"))
+    } else {
+        Ok(text)
+    }
+}
+
+fn value(flag: &str, value: Option<String>) -> Result<String, String> {
+    value.ok_or_else(|| format!("{flag} expects a value"))
+}
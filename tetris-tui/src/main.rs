@@ -1,19 +1,101 @@
+mod ai;
+mod ascii;
+mod audio;
+mod config;
+mod panel;
+mod replay;
+mod scores;
+mod screenshot;
+mod share;
+mod spectator;
+
+use ai::Demo;
+use audio::{MAX_VOLUME, muted, play_sound, set_muted, set_volume, toggle_mute, volume};
+use config::{Config, KeyConfig};
 use crossterm::{
     cursor,
-    event::{self, Event, KeyCode},
+    event::{
+        self, DisableFocusChange, EnableFocusChange, Event, KeyCode, KeyEvent, KeyEventKind,
+        KeyModifiers, KeyboardEnhancementFlags, PopKeyboardEnhancementFlags,
+        PushKeyboardEnhancementFlags,
+    },
     execute, queue,
-    style::{Color, Print, SetForegroundColor},
+    style::{Attribute, Color, Print, SetAttribute, SetForegroundColor},
     terminal::{self, ClearType},
 };
-use rand::Rng;
+use panel::{Panel, Segment};
+use rand::{Rng, RngCore, SeedableRng, rngs::ChaCha12Rng, seq::SliceRandom};
+use replay::{Entry, Input, Recorder, Replay};
+use scores::Pace;
+use spectator::Spectator;
 use std::{
-    io::{self, Write},
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufWriter, Write},
+    panic,
+    path::Path,
+    str::FromStr,
     time::{Duration, Instant},
 };
 
 const WIDTH: usize = 10;
 const HEIGHT: usize = 20;
 const BLOCK: &str = "██";
+const DANGER_ROWS: usize = 4;
+const BULLET_TIME_ROWS: usize = 2;
+const BUFFER_ROWS: usize = 2;
+const ROWS: usize = HEIGHT + BUFFER_ROWS;
+const TARGET_FPS: u64 = 60;
+const FRAME_TIME: Duration = Duration::from_micros(1_000_000 / TARGET_FPS);
+const MIN_IDLE: Duration = Duration::from_millis(1);
+const MAX_FRAME_STEP: Duration = Duration::from_millis(250);
+const PANEL_WIDTH: usize = 28;
+const POWERUP_ROWS: usize = 4;
+const POPUP_TTL: Duration = Duration::from_millis(900);
+const POPUP_RISE: Duration = Duration::from_millis(300);
+const TRAIL_TTL: Duration = Duration::from_millis(150);
+const RAINBOW_PERIOD: Duration = Duration::from_secs(6);
+const GHOST_BLINK: Duration = Duration::from_millis(200);
+const BURST_TTL: Duration = Duration::from_millis(300);
+const SWAP_TTL: Duration = Duration::from_millis(200);
+const SLOW_TIME: Duration = Duration::from_secs(10);
+const SLOW_TIME_MAX_STACKS: u32 = 4;
+const WELL_TIME: Duration = Duration::from_secs(15);
+const TILT_PIECES: u32 = 3;
+const CHAOS_START: f32 = 0.5;
+const CHAOS_PER_LINE: f32 = 0.04;
+const CHAOS_PER_COMBO: f32 = 0.03;
+const CHAOS_DECAY: f32 = 0.01;
+const CHAOS_DANGER: f32 = 0.05;
+const GARBAGE_DELAY: Duration = Duration::from_millis(1500);
+const STATUS_ROWS: u16 = 3;
+const MIN_FALL_MS: u64 = 20;
+const LINEAR_STEP_MS: u64 = 40;
+const NES_FRAME_MS: f64 = 1000.0 / 60.0988;
+const NES_FRAMES: [u64; 19] = [
+    48, 43, 38, 33, 28, 23, 18, 13, 8, 6, 5, 5, 5, 4, 4, 4, 3, 3, 3,
+];
+const SOFT_DROP_HOLD: Duration = Duration::from_millis(120);
+const PEEK_HOLD: Duration = Duration::from_millis(600);
+const REPEAT_GAP: Duration = Duration::from_millis(100);
+const RESET_PENALTY: u32 = 500;
+const CHEESE_ROWS: u32 = 10;
+const SCENARIOS: [&[&str]; 3] = [
+    &["##........", "#...######", "##.#######"],
+    &["##........", "#...#####.", "##.#######"],
+    &[
+        "##........",
+        "#...######",
+        "##.#######",
+        "#########.",
+        ".#########",
+    ],
+];
+const PIECE_SEED_SALT: u64 = 0x9e37_79b9_7f4a_7c15;
+const SURVIVAL_START: Duration = Duration::from_secs(10);
+const SURVIVAL_MIN: Duration = Duration::from_secs(1);
+const SURVIVAL_HALF_LIFE: f64 = 120.0;
+const DEMO_RESTART: Duration = Duration::from_secs(3);
 
 #[derive(Clone, Copy, PartialEq)]
 enum TetrominoType {
@@ -26,6 +108,30 @@ enum TetrominoType {
     L,
 }
 
+impl TetrominoType {
+    const ALL: [TetrominoType; 7] = [
+        TetrominoType::I,
+        TetrominoType::O,
+        TetrominoType::T,
+        TetrominoType::S,
+        TetrominoType::Z,
+        TetrominoType::J,
+        TetrominoType::L,
+    ];
+
+    fn name(self) -> char {
+        match self {
+            TetrominoType::I => 'I',
+            TetrominoType::O => 'O',
+            TetrominoType::T => 'T',
+            TetrominoType::S => 'S',
+            TetrominoType::Z => 'Z',
+            TetrominoType::J => 'J',
+            TetrominoType::L => 'L',
+        }
+    }
+}
+
 #[derive(Clone, Copy, PartialEq)]
 enum PowerUpType {
     Bomb,
@@ -33,20 +139,386 @@ enum PowerUpType {
     Ghost,
     Hammer,
     Random,
+    Well,
+    Tilt,
+}
+
+impl PowerUpType {
+    fn name(self) -> &'static str {
+        match self {
+            PowerUpType::Bomb => "BOMBE",
+            PowerUpType::SlowTime => "RALENTI",
+            PowerUpType::Ghost => "FANTÔME",
+            PowerUpType::Hammer => "MARTEAU",
+            PowerUpType::Random => "HASARD",
+            PowerUpType::Well => "PUITS",
+            PowerUpType::Tilt => "GRAVITÉ",
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            PowerUpType::Bomb => Color::Red,
+            PowerUpType::SlowTime => Color::Cyan,
+            PowerUpType::Ghost => Color::White,
+            PowerUpType::Hammer => Color::Yellow,
+            PowerUpType::Random => Color::Magenta,
+            PowerUpType::Well => Color::Green,
+            PowerUpType::Tilt => Color::Blue,
+        }
+    }
 }
 
 #[derive(Clone, Copy, PartialEq)]
 enum CellType {
-    Normal(Color),
+    Normal(Color, Duration),
+    Garbage,
     Obstacle,
     PowerUp(PowerUpType),
 }
 
+#[derive(Clone, Copy, PartialEq)]
+enum Action {
+    Left,
+    Right,
+    SoftDrop,
+    Rotate,
+    HardDrop,
+    Hold,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum RotationSystem {
+    Simple,
+    Srs,
+}
+
+impl RotationSystem {
+    fn kicks(self, typ: TetrominoType, from: usize) -> &'static [(i32, i32)] {
+        const JLSTZ: [[(i32, i32); 5]; 4] = [
+            [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+            [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+            [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+            [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+        ];
+        const I: [[(i32, i32); 5]; 4] = [
+            [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)],
+            [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)],
+            [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)],
+            [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)],
+        ];
+
+        match (self, typ) {
+            (RotationSystem::Simple, _) | (RotationSystem::Srs, TetrominoType::O) => &[(0, 0)],
+            (RotationSystem::Srs, TetrominoType::I) => &I[from],
+            (RotationSystem::Srs, _) => &JLSTZ[from],
+        }
+    }
+}
+
+impl FromStr for RotationSystem {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "simple" => Ok(RotationSystem::Simple),
+            "srs" => Ok(RotationSystem::Srs),
+            _ => Err(format!("unknown rotation system: {s}")),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Theme {
+    Classic,
+    Soft,
+    Neon,
+    Gold,
+}
+
+const THEMES: [Theme; 4] = [Theme::Classic, Theme::Soft, Theme::Neon, Theme::Gold];
+
+impl Theme {
+    fn name(self) -> &'static str {
+        match self {
+            Theme::Classic => "Classique",
+            Theme::Soft => "Doux",
+            Theme::Neon => "Néon",
+            Theme::Gold => "Or",
+        }
+    }
+
+    fn key(self) -> &'static str {
+        match self {
+            Theme::Classic => "classic",
+            Theme::Soft => "soft",
+            Theme::Neon => "neon",
+            Theme::Gold => "gold",
+        }
+    }
+
+    fn unlock_score(self) -> u32 {
+        match self {
+            Theme::Classic => 0,
+            Theme::Soft => 2000,
+            Theme::Neon => 5000,
+            Theme::Gold => 10000,
+        }
+    }
+
+    fn tint(self, color: Color) -> Color {
+        let palette = match self {
+            Theme::Classic => return color,
+            Theme::Soft => [
+                (150, 220, 230),
+                (240, 230, 150),
+                (200, 160, 220),
+                (170, 220, 160),
+                (235, 150, 150),
+                (150, 170, 230),
+                (240, 190, 140),
+            ],
+            Theme::Neon => [
+                (0, 255, 255),
+                (255, 255, 0),
+                (255, 0, 255),
+                (57, 255, 20),
+                (255, 20, 80),
+                (40, 120, 255),
+                (255, 140, 0),
+            ],
+            Theme::Gold => [
+                (255, 215, 0),
+                (238, 232, 170),
+                (218, 165, 32),
+                (240, 230, 140),
+                (184, 134, 11),
+                (205, 133, 63),
+                (255, 193, 37),
+            ],
+        };
+        let index = match color {
+            Color::Cyan => 0,
+            Color::Yellow => 1,
+            Color::Magenta => 2,
+            Color::Green => 3,
+            Color::Red => 4,
+            Color::Blue => 5,
+            Color::White => 6,
+            color => return color,
+        };
+        let (r, g, b) = palette[index];
+        Color::Rgb { r, g, b }
+    }
+}
+
+impl FromStr for Theme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        THEMES
+            .into_iter()
+            .find(|theme| theme.key() == s)
+            .ok_or_else(|| format!("unknown theme: {s}"))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum GameMode {
+    Classic,
+    Invisible,
+    Cheese(u32),
+    Survival,
+    PerfectClear,
+}
+
+impl GameMode {
+    fn key(self) -> String {
+        match self {
+            GameMode::Classic => "classic".to_string(),
+            GameMode::Invisible => "invisible".to_string(),
+            GameMode::Cheese(rows) => format!("cheese{rows}"),
+            GameMode::Survival => "survival".to_string(),
+            GameMode::PerfectClear => "perfect".to_string(),
+        }
+    }
+
+    fn label(self) -> String {
+        match self {
+            GameMode::Classic => "classique".to_string(),
+            GameMode::Invisible => "invisible".to_string(),
+            GameMode::Cheese(rows) => format!("cheese {rows}"),
+            GameMode::Survival => "survie".to_string(),
+            GameMode::PerfectClear => "perfect clear".to_string(),
+        }
+    }
+
+    fn pace_key(self) -> Option<String> {
+        matches!(self, GameMode::Cheese(_) | GameMode::Survival).then(|| self.key())
+    }
+}
+
+impl FromStr for GameMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "classic" => Ok(GameMode::Classic),
+            "invisible" => Ok(GameMode::Invisible),
+            "cheese" => Ok(GameMode::Cheese(CHEESE_ROWS)),
+            "survival" => Ok(GameMode::Survival),
+            "perfect" => Ok(GameMode::PerfectClear),
+            _ => match s.strip_prefix("cheese:").map(str::parse) {
+                Some(Ok(rows @ 1..=100)) => Ok(GameMode::Cheese(rows)),
+                Some(_) => Err(format!("cheese expects 1 to 100 rows: {s}")),
+                None => Err(format!("unknown mode: {s}")),
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Layout {
+    Side,
+    Bottom,
+    Both,
+}
+
+impl Layout {
+    fn side(self) -> bool {
+        self != Layout::Bottom
+    }
+
+    fn bottom(self) -> bool {
+        self != Layout::Side
+    }
+}
+
+impl FromStr for Layout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "side" => Ok(Layout::Side),
+            "bottom" => Ok(Layout::Bottom),
+            "both" => Ok(Layout::Both),
+            _ => Err(format!("unknown layout: {s}")),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum BombShape {
+    Square,
+    Plus,
+    Diamond,
+}
+
+impl BombShape {
+    fn covers(self, dx: i32, dy: i32, radius: i32) -> bool {
+        match self {
+            BombShape::Square => true,
+            BombShape::Plus => dx == 0 || dy == 0,
+            BombShape::Diamond => dx.abs() + dy.abs() <= radius,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq)]
+enum SpeedCurve {
+    Exponential,
+    Linear,
+    Classic,
+    Custom(Vec<u64>),
+}
+
+impl SpeedCurve {
+    fn fall_ms(&self, level: u32, fall_ms: u64, factor: f64) -> u64 {
+        let index = level.saturating_sub(1);
+        match self {
+            SpeedCurve::Exponential => (fall_ms as f64 * factor.powi(index as i32)) as u64,
+            SpeedCurve::Linear => fall_ms.saturating_sub(index as u64 * LINEAR_STEP_MS),
+            SpeedCurve::Classic => {
+                let frames = match index as usize {
+                    i if i < NES_FRAMES.len() => NES_FRAMES[i],
+                    19..=28 => 2,
+                    _ => 1,
+                };
+                (frames as f64 * NES_FRAME_MS) as u64
+            }
+            SpeedCurve::Custom(table) => table
+                .get(index as usize)
+                .or(table.last())
+                .copied()
+                .unwrap_or(fall_ms),
+        }
+    }
+}
+
+impl FromStr for SpeedCurve {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "exponential" => Ok(SpeedCurve::Exponential),
+            "linear" => Ok(SpeedCurve::Linear),
+            "classic" => Ok(SpeedCurve::Classic),
+            _ => match s.strip_prefix("custom:") {
+                Some(table) => table
+                    .split(',')
+                    .map(|ms| ms.trim().parse::<u64>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .ok()
+                    .filter(|table| !table.is_empty())
+                    .map(SpeedCurve::Custom)
+                    .ok_or_else(|| {
+                        format!("custom curve expects comma-separated milliseconds: {s}")
+                    }),
+                None => Err(format!("unknown speed curve: {s}")),
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum SlowTimeRule {
+    Refresh,
+    Extend,
+    Stack,
+}
+
+impl FromStr for SlowTimeRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "refresh" => Ok(SlowTimeRule::Refresh),
+            "extend" => Ok(SlowTimeRule::Extend),
+            "stack" => Ok(SlowTimeRule::Stack),
+            _ => Err(format!("unknown slow time rule: {s}")),
+        }
+    }
+}
+
+impl FromStr for BombShape {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "square" => Ok(BombShape::Square),
+            "plus" => Ok(BombShape::Plus),
+            "diamond" => Ok(BombShape::Diamond),
+            _ => Err(format!("unknown bomb shape: {s}")),
+        }
+    }
+}
+
 #[derive(Clone)]
 struct Tetromino {
     shape: Vec<Vec<bool>>,
     color: Color,
     typ: TetrominoType,
+    rotation: usize,
 }
 
 impl Tetromino {
@@ -103,89 +575,411 @@ impl Tetromino {
                 Color::White,
             ),
         };
-        Tetromino { shape, color, typ }
+        Tetromino {
+            shape,
+            color,
+            typ,
+            rotation: 0,
+        }
+    }
+
+    fn is_pivot(&self, i: usize, j: usize) -> bool {
+        let n = self.shape.len();
+        let center = [(n - 1) / 2, n / 2];
+        self.typ != TetrominoType::O && center.contains(&i) && center.contains(&j)
+    }
+
+    fn covers(&self, px: i32, py: i32, x: usize, y: usize) -> bool {
+        let (i, j) = (y as i32 - py, x as i32 - px);
+        i >= 0
+            && j >= 0
+            && self
+                .shape
+                .get(i as usize)
+                .and_then(|row| row.get(j as usize))
+                .copied()
+                .unwrap_or(false)
+    }
+
+    fn spawn_x(&self, width: usize) -> i32 {
+        ((width - self.shape.len()) / 2) as i32
     }
 
     fn rotate(&mut self) {
         let n = self.shape.len();
         let mut rotated = vec![vec![false; n]; n];
-        for i in 0..n {
-            for j in 0..n {
-                rotated[j][n - 1 - i] = self.shape[i][j];
+        for (i, row) in self.shape.iter().enumerate() {
+            for (j, &cell) in row.iter().enumerate() {
+                rotated[j][n - 1 - i] = cell;
             }
         }
         self.shape = rotated;
+        self.rotation = (self.rotation + 1) % 4;
+    }
+}
+
+#[derive(Clone, Default)]
+struct Stats {
+    holds: u32,
+    pieces: u32,
+    piece_time: Duration,
+    wasted_rotations: u32,
+    line_points: u32,
+    combo_points: u32,
+    bomb_points: u32,
+    hammer_points: u32,
+    drop_points: u32,
+    cleanup_points: u32,
+    perfect_clears: u32,
+    perfect_points: u32,
+    penalty_points: u32,
+}
+
+#[derive(Clone, Copy)]
+enum ScoreSource {
+    Lines,
+    Combo,
+    Bomb,
+    Hammer,
+    Drop,
+    Cleanup,
+    Perfect,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum MenuItem {
+    Resume,
+    Restart,
+    Sound,
+    Grid,
+    Settings,
+    Quit,
+}
+
+const MENU: [MenuItem; 6] = [
+    MenuItem::Resume,
+    MenuItem::Restart,
+    MenuItem::Sound,
+    MenuItem::Grid,
+    MenuItem::Settings,
+    MenuItem::Quit,
+];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Setting {
+    Sound,
+    Volume,
+    Ghost,
+    Grid,
+    Speed,
+    FirstHold,
+    HoldSpawn,
+    Theme,
+    Back,
+}
+
+const SETTINGS: [Setting; 9] = [
+    Setting::Sound,
+    Setting::Volume,
+    Setting::Ghost,
+    Setting::Grid,
+    Setting::Speed,
+    Setting::FirstHold,
+    Setting::HoldSpawn,
+    Setting::Theme,
+    Setting::Back,
+];
+
+const SPEED_STEP_MS: u64 = 50;
+const SPEED_RANGE_MS: (u64, u64) = (50, 2000);
+
+impl Setting {
+    fn label(self, game: &Game) -> String {
+        let toggle = |on: bool| if on { "oui" } else { "non" };
+        match self {
+            Setting::Sound => format!("Son: {}", toggle(!muted())),
+            Setting::Volume => format!(
+                "Volume: {}{}",
+                "▰".repeat(volume() as usize),
+                "▱".repeat((MAX_VOLUME - volume()) as usize)
+            ),
+            Setting::Ghost => format!(
+                "Fantôme: {}",
+                if game.config.ghost_on_demand {
+                    "touche"
+                } else {
+                    "visible"
+                }
+            ),
+            Setting::Grid => format!("Grille: {}", toggle(game.config.show_grid)),
+            Setting::Speed => format!("Chute: {} ms", game.config.fall_ms),
+            Setting::FirstHold => format!("1re réserve: {}", toggle(game.config.hold_first_piece)),
+            Setting::HoldSpawn => format!(
+                "Échange: {}",
+                if game.config.hold_next_tick {
+                    "différé"
+                } else {
+                    "direct"
+                }
+            ),
+            Setting::Theme => {
+                let theme = game.config.theme;
+                if theme.unlock_score() <= game.config.top_score {
+                    format!("Thème: {}", theme.name())
+                } else {
+                    format!("Thème: {} {}", theme.name(), theme.unlock_score())
+                }
+            }
+            Setting::Back => "Retour".to_string(),
+        }
+    }
+
+    fn locked(self, game: &Game) -> bool {
+        self == Setting::Theme && game.theme() != game.config.theme
+    }
+}
+
+impl MenuItem {
+    fn label(self, game: &Game) -> String {
+        let toggle = |on: bool| if on { "oui" } else { "non" };
+        match self {
+            MenuItem::Resume => "Reprendre".to_string(),
+            MenuItem::Restart => "Recommencer".to_string(),
+            MenuItem::Sound => format!("Son: {}", toggle(!muted())),
+            MenuItem::Grid => format!("Grille: {}", toggle(game.config.show_grid)),
+            MenuItem::Settings => "Réglages".to_string(),
+            MenuItem::Quit => "Quitter".to_string(),
+        }
     }
 }
 
+#[derive(Clone)]
+struct Popup {
+    text: String,
+    color: Color,
+    row: usize,
+    spawned: Duration,
+}
+
+#[derive(Clone)]
+struct Trail {
+    cells: Vec<(usize, usize)>,
+    color: Color,
+    spawned: Duration,
+}
+
+#[derive(Clone)]
+struct HoldSwap {
+    piece: Tetromino,
+    x: i32,
+    y: i32,
+    spawned: Duration,
+}
+
+#[derive(Clone)]
+struct Burst {
+    x: usize,
+    y: usize,
+    color: Color,
+    spawned: Duration,
+}
+
+#[derive(Clone)]
 struct Game {
+    config: Config,
     board: Vec<Vec<Option<CellType>>>,
     current: Tetromino,
     current_x: i32,
     current_y: i32,
-    next: Tetromino,
+    next: VecDeque<Tetromino>,
+    pieces: Randomizer,
+    hold: Option<TetrominoType>,
+    can_hold: bool,
+    hold_pending: bool,
+    hold_preview: bool,
+    hold_ready_at: Duration,
+    garbage_out: u32,
+    pending_garbage: u32,
+    garbage_due: Option<Duration>,
+    garbage_gap: Option<usize>,
+    reset_pending: bool,
+    piece_spawned_at: Duration,
+    stats: Stats,
     score: u32,
     combo: u32,
+    combo_window: Duration,
+    chaos: f32,
     game_over: bool,
+    won: bool,
+    cheese_pending: u32,
+    survival_rise_at: Duration,
+    paused: bool,
+    menu: usize,
+    settings: Option<usize>,
+    beaten_best: Option<u32>,
+    unlocked_themes: Vec<Theme>,
+    pace_log: Vec<u32>,
     ghost_mode: bool,
     ghost_remaining: u32,
+    ghost_end: Option<Duration>,
     slow_time_active: bool,
-    slow_time_end: Option<Instant>,
+    slow_time_stacks: u32,
+    slow_time_end: Option<Duration>,
+    well_until: Option<Duration>,
+    tilt: i32,
+    tilt_remaining: u32,
+    charge: i32,
     hammer_mode: bool,
-    last_clear_time: Option<Instant>,
+    hammer_armed_at: Duration,
+    hammers: u32,
+    forced_next: Option<TetrominoType>,
+    last_clear_time: Option<Duration>,
+    last_powerup_time: Duration,
     lines_cleared_total: u32,
+    danger: bool,
+    clock: Duration,
+    fall_accumulator: Duration,
+    lock_at: Option<Duration>,
+    grounded_at: Option<Duration>,
+    lowest_y: i32,
+    drop_tapped_at: Option<Duration>,
+    soft_dropping: bool,
+    soft_drop_until: Option<Duration>,
+    soft_drop_timeout: Option<Duration>,
+    peeking: bool,
+    peek_until: Option<Duration>,
+    popups: Vec<Popup>,
+    trails: Vec<Trail>,
+    hold_swap: Option<HoldSwap>,
+    bursts: Vec<Burst>,
+    seed: u64,
+    rng: GameRng,
 }
 
 impl Game {
-    fn new() -> Self {
-        let mut rng = rand::rng();
-        let types = [
-            TetrominoType::I,
-            TetrominoType::O,
-            TetrominoType::T,
-            TetrominoType::S,
-            TetrominoType::Z,
-            TetrominoType::J,
-            TetrominoType::L,
-        ];
+    fn new(config: Config) -> Self {
+        let seed = config.seed.unwrap_or_else(rand::random);
+        let rng = GameRng(ChaCha12Rng::seed_from_u64(seed));
+        let mut pieces = Randomizer::new(seed, config.bag);
+        let current = Tetromino::new(pieces.next());
+        let next = (0..config.preview_depth.max(1))
+            .map(|_| Tetromino::new(pieces.next()))
+            .collect();
 
-        Game {
-            board: vec![vec![None; WIDTH]; HEIGHT],
-            current: Tetromino::new(types[rng.random_range(0..7)]),
-            current_x: (WIDTH / 2 - 2) as i32,
+        let combo_window = config.combo_window;
+        let mut game = Game {
+            config,
+            board: vec![vec![None; WIDTH]; ROWS],
+            current_x: current.spawn_x(WIDTH),
+            current,
             current_y: 0,
-            next: Tetromino::new(types[rng.random_range(0..7)]),
+            next,
+            pieces,
+            hold: None,
+            can_hold: true,
+            hold_pending: false,
+            hold_preview: false,
+            hold_ready_at: Duration::ZERO,
+            garbage_out: 0,
+            pending_garbage: 0,
+            garbage_due: None,
+            garbage_gap: None,
+            reset_pending: false,
+            piece_spawned_at: Duration::ZERO,
+            stats: Stats::default(),
             score: 0,
             combo: 0,
+            combo_window,
+            chaos: CHAOS_START,
             game_over: false,
+            won: false,
+            cheese_pending: 0,
+            survival_rise_at: SURVIVAL_START,
+            paused: false,
+            menu: 0,
+            settings: None,
+            beaten_best: None,
+            unlocked_themes: Vec::new(),
+            pace_log: Vec::new(),
             ghost_mode: false,
             ghost_remaining: 0,
+            ghost_end: None,
             slow_time_active: false,
+            slow_time_stacks: 0,
             slow_time_end: None,
+            well_until: None,
+            tilt: 0,
+            tilt_remaining: 0,
+            charge: 0,
             hammer_mode: false,
+            hammer_armed_at: Duration::ZERO,
+            hammers: 0,
+            forced_next: None,
             last_clear_time: None,
+            last_powerup_time: Duration::ZERO,
             lines_cleared_total: 0,
+            danger: false,
+            clock: Duration::ZERO,
+            fall_accumulator: Duration::ZERO,
+            lock_at: None,
+            grounded_at: None,
+            lowest_y: 0,
+            drop_tapped_at: None,
+            soft_dropping: false,
+            soft_drop_until: None,
+            soft_drop_timeout: Some(SOFT_DROP_HOLD),
+            peeking: false,
+            peek_until: None,
+            popups: Vec::new(),
+            trails: Vec::new(),
+            hold_swap: None,
+            bursts: Vec::new(),
+            seed,
+            rng,
+        };
+        game.can_hold = game.config.hold_first_piece;
+        if game.config.scenario.is_some() {
+            game.load_scenario();
+        }
+        if let Some(rows) = &game.config.start_board {
+            for (y, row) in (ROWS - rows.len()..).zip(rows) {
+                game.board[y].clone_from(row);
+            }
         }
+        if let GameMode::Cheese(rows) = game.config.mode {
+            game.cheese_pending = rows;
+            game.deal_cheese();
+        }
+        game.enter_field();
+        game
     }
 
     fn can_move(&self, dx: i32, dy: i32) -> bool {
-        for (i, row) in self.current.shape.iter().enumerate() {
+        self.fits(&self.current, self.current_x + dx, self.current_y + dy)
+    }
+
+    fn fits(&self, piece: &Tetromino, px: i32, py: i32) -> bool {
+        for (i, row) in piece.shape.iter().enumerate() {
             for (j, &cell) in row.iter().enumerate() {
                 if cell {
-                    let new_x = self.current_x + j as i32 + dx;
-                    let new_y = self.current_y + i as i32 + dy;
+                    let new_x = px + j as i32;
+                    let new_y = py + i as i32;
 
-                    if new_x < 0 || new_x >= WIDTH as i32 || new_y >= HEIGHT as i32 {
+                    if new_x < 0
+                        || new_x >= self.width() as i32
+                        || new_y < 0
+                        || new_y >= ROWS as i32
+                    {
                         return false;
                     }
 
-                    if new_y >= 0 {
-                        if let Some(cell_type) = &self.board[new_y as usize][new_x as usize] {
-                            match cell_type {
-                                CellType::Obstacle if !self.ghost_mode => return false,
-                                CellType::Normal(_) if !self.ghost_mode => return false,
-                                _ => {}
+                    if let Some(cell_type) = &self.board[new_y as usize][new_x as usize] {
+                        match cell_type {
+                            CellType::Obstacle if !self.ghost_mode => return false,
+                            CellType::Normal(..) | CellType::Garbage if !self.ghost_mode => {
+                                return false;
                             }
+                            _ => {}
                         }
                     }
                 }
@@ -194,95 +988,223 @@ impl Game {
         true
     }
 
+    fn overlaps_solid(&self, py: i32) -> bool {
+        self.current.shape.iter().enumerate().any(|(i, row)| {
+            row.iter().enumerate().any(|(j, &cell)| {
+                let x = self.current_x + j as i32;
+                let y = py + i as i32;
+                cell && y >= 0
+                    && matches!(
+                        self.board[y as usize][x as usize],
+                        Some(CellType::Normal(..) | CellType::Garbage | CellType::Obstacle)
+                    )
+            })
+        })
+    }
+
     fn move_piece(&mut self, dx: i32, dy: i32) -> bool {
         if self.can_move(dx, dy) {
             self.current_x += dx;
             self.current_y += dy;
+            self.track_ground();
             true
         } else {
             false
         }
     }
 
+    fn track_ground(&mut self) {
+        if self.current_y > self.lowest_y {
+            self.lowest_y = self.current_y;
+            self.grounded_at = None;
+        }
+        if !self.can_move(0, 1) {
+            self.grounded_at.get_or_insert(self.clock);
+        }
+    }
+
+    fn lock_timed_out(&self) -> bool {
+        !self.config.lock_timeout.is_zero()
+            && self
+                .grounded_at
+                .is_some_and(|at| self.clock >= at + self.config.lock_timeout)
+    }
+
     fn rotate_piece(&mut self) {
         let mut rotated = self.current.clone();
         rotated.rotate();
-        let old = self.current.clone();
-        self.current = rotated;
+        let old = std::mem::replace(&mut self.current, rotated);
+        let (old_x, old_y) = (self.current_x, self.current_y);
+
+        for &(dx, dy) in self.config.rotation.kicks(old.typ, old.rotation) {
+            self.current_x = old_x + dx;
+            self.current_y = old_y - dy;
+            if self.can_move(0, 0) {
+                self.track_ground();
+                self.play_note(300, 4 + self.current.rotation as i32, 30);
+                return;
+            }
+        }
+
+        self.current = old;
+        self.current_x = old_x;
+        self.current_y = old_y;
+        self.stats.wasted_rotations += 1;
+    }
+
+    fn hold_cooldown(&self) -> Duration {
+        self.hold_ready_at.saturating_sub(self.clock)
+    }
 
-        if !self.can_move(0, 0) {
-            self.current = old;
+    fn hold_piece(&mut self) {
+        self.hold_preview = false;
+        if self.config.no_hold || !self.can_hold || !self.hold_cooldown().is_zero() {
+            return;
+        }
+        if self.config.hold_next_tick {
+            self.hold_pending = true;
         } else {
-            play_sound(300, 30);
+            self.swap_hold();
+        }
+    }
+
+    fn swap_hold(&mut self) {
+        self.hold_swap = Some(HoldSwap {
+            piece: self.current.clone(),
+            x: self.current_x,
+            y: self.current_y,
+            spawned: self.clock,
+        });
+        let held = self.hold.replace(self.current.typ);
+        match held {
+            Some(typ) => {
+                self.current = Tetromino::new(typ);
+                self.enter_field();
+            }
+            None => self.spawn_new_piece(),
         }
+
+        self.can_hold = false;
+        self.hold_ready_at = self.clock + self.config.hold_cooldown;
+        self.penalize(self.config.hold_cost);
+        self.stats.holds += 1;
+        self.play_note(350, 2, 30);
     }
 
     fn lock_piece(&mut self) {
+        self.lock_at = None;
+        self.stats.pieces += 1;
+        self.stats.piece_time += self.clock - self.piece_spawned_at;
+        self.can_hold = true;
+
+        if self.ghost_mode && self.config.ghost_solid_lock {
+            while self.overlaps_solid(self.current_y) {
+                self.current_y -= 1;
+            }
+        }
+
+        if self.ghost_mode && self.ghost_remaining > 0 {
+            self.ghost_remaining -= 1;
+            if self.ghost_remaining == 0 {
+                self.ghost_mode = false;
+            }
+        }
+
+        self.collect_power_ups();
+
+        let mut visible = false;
         for (i, row) in self.current.shape.iter().enumerate() {
             for (j, &cell) in row.iter().enumerate() {
                 if cell {
                     let x = (self.current_x + j as i32) as usize;
                     let y = (self.current_y + i as i32) as usize;
-                    if y < HEIGHT {
-                        self.board[y][x] = Some(CellType::Normal(self.current.color));
-                    }
+                    self.board[y][x] = Some(CellType::Normal(self.current.color, self.clock));
+                    visible |= y >= BUFFER_ROWS;
                 }
             }
         }
+        if !visible {
+            self.top_out();
+            return;
+        }
 
-        let freq = match self.current.typ {
-            TetrominoType::I => 440,
-            TetrominoType::O => 494,
-            TetrominoType::T => 523,
-            TetrominoType::S => 587,
-            TetrominoType::Z => 659,
-            TetrominoType::J => 698,
-            TetrominoType::L => 784,
+        let (freq, degree) = match self.current.typ {
+            TetrominoType::I => (440, 0),
+            TetrominoType::O => (494, 1),
+            TetrominoType::T => (523, 2),
+            TetrominoType::S => (587, 3),
+            TetrominoType::Z => (659, 4),
+            TetrominoType::J => (698, 5),
+            TetrominoType::L => (784, 6),
         };
-        play_sound(freq, 50);
+        self.play_note(freq, degree, 50);
 
-        self.collect_power_ups();
+        if self.tilt != 0 {
+            self.apply_gravity();
+            self.tilt_remaining = self.tilt_remaining.saturating_sub(1);
+            if self.tilt_remaining == 0 {
+                self.tilt = 0;
+            }
+        }
         self.clear_lines();
         self.spawn_new_piece();
 
-        if self.ghost_mode && self.ghost_remaining > 0 {
-            self.ghost_remaining -= 1;
-            if self.ghost_remaining == 0 {
-                self.ghost_mode = false;
-            }
+        let danger = self.in_danger();
+        if danger && !self.danger {
+            play_sound(220, 80);
         }
+        if danger {
+            self.shift_chaos(-CHAOS_DANGER);
+        }
+        self.danger = danger;
     }
 
-    fn collect_power_ups(&mut self) {
-        let shape = self.current.shape.clone();
-        let current_x = self.current_x;
-        let current_y = self.current_y;
-        let color = self.current.color;
+    fn shift_chaos(&mut self, amount: f32) {
+        self.chaos = (self.chaos + amount).clamp(0.0, 1.0);
+    }
+
+    fn spawn_chance(&self, chance: f64, scale: f32) -> f64 {
+        if self.config.chaos {
+            (chance * scale as f64).clamp(0.0, 1.0)
+        } else {
+            chance
+        }
+    }
 
+    fn collect_power_ups(&mut self) {
         let mut power_ups_to_activate = Vec::new();
 
-        for (i, row) in shape.iter().enumerate() {
+        for (i, row) in self.current.shape.iter().enumerate() {
             for (j, &cell) in row.iter().enumerate() {
                 if cell {
-                    let x = (current_x + j as i32) as usize;
-                    let y = (current_y + i as i32) as usize;
-                    if y < HEIGHT {
-                        if let Some(CellType::PowerUp(powerup)) = self.board[y][x] {
-                            power_ups_to_activate.push((x, y, powerup));
-                        }
+                    let x = (self.current_x + j as i32) as usize;
+                    let y = (self.current_y + i as i32) as usize;
+                    if y < ROWS
+                        && let Some(CellType::PowerUp(powerup)) = self.board[y][x]
+                    {
+                        power_ups_to_activate.push((x, y, powerup));
                     }
                 }
             }
         }
 
-        for (x, y, power_up) in power_ups_to_activate {
-            self.activate_power_up(power_up);
-            self.board[y][x] = Some(CellType::Normal(color));
+        for &(x, y, powerup) in &power_ups_to_activate {
+            self.board[y][x] = None;
+            self.bursts.push(Burst {
+                x,
+                y,
+                color: powerup.color(),
+                spawned: self.clock,
+            });
+        }
+        for (_, y, power_up) in power_ups_to_activate {
+            self.activate_power_up(power_up, y);
         }
     }
 
-    fn activate_power_up(&mut self, powerup: PowerUpType) {
-        play_sound(800, 100);
+    fn activate_power_up(&mut self, powerup: PowerUpType, row: usize) {
+        self.play_note(800, 9, 100);
+        self.add_popup(powerup.name().to_string(), powerup.color(), row);
 
         match powerup {
             PowerUpType::Bomb => {
@@ -302,51 +1224,174 @@ impl Game {
                     cx /= count;
                     cy /= count;
 
-                    for dy in -2..=2 {
-                        for dx in -2..=2 {
+                    let radius = self.config.bomb_radius.min(ROWS as u64) as i32;
+                    for dy in -radius..=radius {
+                        for dx in -radius..=radius {
+                            if !self.config.bomb_shape.covers(dx, dy, radius) {
+                                continue;
+                            }
                             let x = (cx + dx) as usize;
                             let y = (cy + dy) as usize;
-                            if x < WIDTH && y < HEIGHT {
-                                if let Some(CellType::Normal(_)) = self.board[y][x] {
-                                    self.board[y][x] = None;
-                                    self.score += 10;
-                                }
+                            if x >= self.width() || y >= ROWS {
+                                continue;
                             }
+                            let points = match self.board[y][x] {
+                                Some(CellType::Normal(..) | CellType::Garbage) => {
+                                    self.config.scoring.bomb_cell
+                                }
+                                Some(CellType::Obstacle) if self.config.bomb_clears_obstacles => {
+                                    self.config.obstacle_bomb_points
+                                }
+                                _ => continue,
+                            };
+                            self.board[y][x] = None;
+                            self.award(ScoreSource::Bomb, points);
                         }
                     }
                 }
             }
             PowerUpType::SlowTime => {
+                let end = match (self.config.slow_time_rule, self.slow_time_end) {
+                    (SlowTimeRule::Extend, Some(end)) => end.max(self.clock) + SLOW_TIME,
+                    _ => self.clock + SLOW_TIME,
+                };
+                self.slow_time_stacks = match self.config.slow_time_rule {
+                    SlowTimeRule::Stack => (self.slow_time_stacks + 1).min(SLOW_TIME_MAX_STACKS),
+                    _ => 1,
+                };
                 self.slow_time_active = true;
-                self.slow_time_end = Some(Instant::now() + Duration::from_secs(10));
+                self.slow_time_end = Some(end);
             }
             PowerUpType::Ghost => {
                 self.ghost_mode = true;
-                self.ghost_remaining = 3;
+                if self.config.ghost_duration.is_zero() {
+                    self.ghost_remaining = self.config.ghost_pieces;
+                } else {
+                    self.ghost_end = Some(self.clock + self.config.ghost_duration);
+                }
             }
             PowerUpType::Hammer => {
+                if self.hammer_mode && self.config.keep_cancelled_hammer {
+                    self.hammers += 1;
+                }
                 self.hammer_mode = true;
+                self.hammer_armed_at = self.clock;
             }
             PowerUpType::Random => {
-                let mut rng = rand::rng();
                 let powerups = [
                     PowerUpType::Bomb,
                     PowerUpType::SlowTime,
                     PowerUpType::Ghost,
                     PowerUpType::Hammer,
+                    PowerUpType::Well,
+                    PowerUpType::Tilt,
                 ];
-                self.activate_power_up(powerups[rng.random_range(0..4)]);
+                let powerup = powerups[self.rng.random_range(0..6)];
+                self.activate_power_up(powerup, row + 1);
+            }
+            PowerUpType::Well => {
+                let width = if self.rng.random_bool(0.5) {
+                    WIDTH + 1
+                } else {
+                    WIDTH - 1
+                };
+                if self.well_until.is_some()
+                    || self.resize_well(width)
+                    || self.resize_well(2 * WIDTH - width)
+                {
+                    self.well_until = Some(self.clock + WELL_TIME);
+                }
+            }
+            PowerUpType::Tilt => {
+                self.tilt = if self.rng.random_bool(0.5) { -1 } else { 1 };
+                self.tilt_remaining = TILT_PIECES;
             }
         }
     }
 
+    fn width(&self) -> usize {
+        self.board[0].len()
+    }
+
+    fn goal_line(&self) -> Option<Vec<Segment>> {
+        let goal = self.config.goal?;
+        let lines = self.lines_cleared_total;
+        Some(if lines >= goal {
+            vec![(Color::Green, format!("Objectif atteint: {lines}/{goal}"))]
+        } else {
+            vec![
+                (Color::White, format!("Objectif: {lines}/{goal} ")),
+                (Color::Green, effect_bar(lines as f64 / goal as f64)),
+            ]
+        })
+    }
+
+    fn pace_delta(&self) -> Option<i64> {
+        let pace = self.config.pace.as_ref()?;
+        Some(self.lines_cleared_total as i64 - pace.lines_at(self.clock) as i64)
+    }
+
+    fn pace_record(&self) -> Option<Pace> {
+        self.config.mode.pace_key()?;
+        let better = match (&self.config.pace, self.config.mode) {
+            (_, GameMode::Cheese(_)) if !self.won => false,
+            (None, _) => true,
+            (Some(best), GameMode::Cheese(_)) => self.clock < best.time,
+            (Some(best), _) => self.clock > best.time,
+        };
+        better.then(|| Pace {
+            time: self.clock,
+            lines: self.pace_log.clone(),
+        })
+    }
+
+    fn swap_flash(&self) -> bool {
+        self.config.flashing
+            && self
+                .hold_swap
+                .as_ref()
+                .is_some_and(|swap| self.clock - swap.spawned < SWAP_TTL / 2)
+    }
+
+    fn theme(&self) -> Theme {
+        if self.config.theme.unlock_score() <= self.config.top_score {
+            self.config.theme
+        } else {
+            Theme::Classic
+        }
+    }
+
+    fn resize_well(&mut self, width: usize) -> bool {
+        if self
+            .board
+            .iter()
+            .any(|row| row.iter().skip(width).any(Option::is_some))
+        {
+            return false;
+        }
+        let board = self.board.clone();
+        let x = self.current_x;
+        for row in &mut self.board {
+            row.resize(width, None);
+        }
+        while !self.can_move(0, 0) && self.current_x > 0 {
+            self.current_x -= 1;
+        }
+        if self.can_move(0, 0) {
+            return true;
+        }
+        self.board = board;
+        self.current_x = x;
+        false
+    }
+
     fn clear_lines(&mut self) {
         let mut lines_to_clear = Vec::new();
 
-        for y in 0..HEIGHT {
+        for y in 0..ROWS {
             let full = self.board[y]
                 .iter()
-                .all(|cell| matches!(cell, Some(CellType::Normal(_))));
+                .all(|cell| matches!(cell, Some(CellType::Normal(..) | CellType::Garbage)));
             if full {
                 lines_to_clear.push(y);
             }
@@ -354,12 +1399,12 @@ impl Game {
 
         if !lines_to_clear.is_empty() {
             for i in 0..lines_to_clear.len() {
-                play_sound(800 + (i * 200) as u32, 50);
+                self.play_note(800 + (i * 200) as u32, 7 + 2 * i as i32, 50);
             }
 
-            let now = Instant::now();
+            let now = self.clock;
             if let Some(last) = self.last_clear_time {
-                if now.duration_since(last) < Duration::from_secs(3) {
+                if now - last < self.combo_window {
                     self.combo += 1;
                 } else {
                     self.combo = 0;
@@ -368,53 +1413,193 @@ impl Game {
             self.last_clear_time = Some(now);
 
             let lines_cleared = lines_to_clear.len() as u32;
+            self.garbage_gap = None;
+            let before = self.lines_cleared_total;
             self.lines_cleared_total += lines_cleared;
-
-            let base_score = match lines_cleared {
-                1 => 100,
-                2 => 300,
-                3 => 500,
-                4 => 800,
+            if let Some(goal) = self.config.goal
+                && (before + 1..=self.lines_cleared_total).contains(&goal)
+            {
+                self.add_popup("OBJECTIF ATTEINT !".to_string(), Color::Green, ROWS / 2);
+                play_sound(784, 150);
+            }
+            let attack = match lines_cleared {
+                2 => 1,
+                3 => 2,
+                4 => 4,
                 _ => 0,
             };
-            self.score += base_score * (1 + self.combo);
+            let cancelled = attack.min(self.pending_garbage);
+            self.pending_garbage -= cancelled;
+            if self.pending_garbage == 0 {
+                self.garbage_due = None;
+            }
+            self.garbage_out += attack - cancelled;
+
+            let base_score = self
+                .config
+                .scoring
+                .lines
+                .get(lines_cleared as usize - 1)
+                .copied()
+                .unwrap_or(0);
+            let combo = match self.config.combo_cap {
+                0 => self.combo,
+                cap => self.combo.min(cap - 1),
+            };
+            let scoring = &self.config.scoring;
+            let bonus = if scoring.combo_additive {
+                scoring.combo_bonus * combo * self.level()
+            } else {
+                base_score * combo * scoring.combo_percent / 100
+            };
+            let points = base_score + bonus;
+            self.award(ScoreSource::Lines, base_score);
+            self.award(ScoreSource::Combo, points - base_score);
+
+            let row = lines_to_clear[0];
+            self.add_popup(format!("+{points}"), Color::Yellow, row);
+            if self.combo > 0 {
+                self.add_popup(format!("COMBO x{}", combo + 1), Color::Red, row + 1);
+            }
+            if lines_cleared == 4 {
+                self.add_popup("TETRIS!".to_string(), Color::Magenta, row + 2);
+            }
+
+            let cleanup = lines_to_clear
+                .iter()
+                .filter(|&&y| {
+                    [y.wrapping_sub(1), y + 1].iter().any(|&ny| {
+                        self.board
+                            .get(ny)
+                            .is_some_and(|row| row.contains(&Some(CellType::Obstacle)))
+                    })
+                })
+                .count() as u32
+                * self.config.obstacle_cleanup_points;
+            if cleanup > 0 {
+                self.award(ScoreSource::Cleanup, cleanup);
+                self.add_popup(format!("NETTOYAGE +{cleanup}"), Color::Grey, row + 3);
+            }
 
-            for line in lines_to_clear.iter().rev() {
+            for line in &lines_to_clear {
                 self.board.remove(*line);
-                self.board.insert(0, vec![None; WIDTH]);
+                self.board.insert(0, vec![None; self.width()]);
             }
 
             self.apply_gravity();
+            if self
+                .board
+                .iter()
+                .flatten()
+                .all(|cell| matches!(cell, None | Some(CellType::PowerUp(_))))
+            {
+                let bonus = self.config.scoring.perfect_clear;
+                self.stats.perfect_clears += 1;
+                self.award(ScoreSource::Perfect, bonus);
+                self.add_popup(
+                    "PERFECT CLEAR!".to_string(),
+                    Color::Cyan,
+                    row.saturating_sub(2),
+                );
+                self.add_popup(format!("+{bonus}"), Color::Yellow, row.saturating_sub(1));
+                self.play_note(1047, 14, 200);
+            }
+            self.shift_chaos(
+                CHAOS_PER_LINE * lines_cleared as f32 + CHAOS_PER_COMBO * self.combo as f32,
+            );
 
-            let mut rng = rand::rng();
-            if self.lines_cleared_total % 5 == 0 && rng.random_bool(0.3) {
+            let cheese = matches!(self.config.mode, GameMode::Cheese(_));
+            if cheese {
+                self.deal_cheese();
+            }
+            if !cheese
+                && self.config.mode != GameMode::PerfectClear
+                && self
+                    .lines_cleared_total
+                    .is_multiple_of(self.config.obstacle_every)
+                && self
+                    .rng
+                    .random_bool(self.spawn_chance(self.config.obstacle_chance, 0.5 + self.chaos))
+            {
                 self.spawn_obstacle();
             }
-            if rng.random_bool(0.4) {
+            if self
+                .rng
+                .random_bool(self.spawn_chance(self.config.power_up_chance, 1.5 - self.chaos))
+            {
                 self.spawn_power_up();
             }
         } else {
             self.combo = 0;
+            self.shift_chaos(-CHAOS_DECAY);
         }
     }
 
+    fn award(&mut self, source: ScoreSource, points: u32) {
+        self.score += points;
+        *match source {
+            ScoreSource::Lines => &mut self.stats.line_points,
+            ScoreSource::Combo => &mut self.stats.combo_points,
+            ScoreSource::Bomb => &mut self.stats.bomb_points,
+            ScoreSource::Hammer => &mut self.stats.hammer_points,
+            ScoreSource::Drop => &mut self.stats.drop_points,
+            ScoreSource::Cleanup => &mut self.stats.cleanup_points,
+            ScoreSource::Perfect => &mut self.stats.perfect_points,
+        } += points;
+    }
+
+    fn penalize(&mut self, points: u32) {
+        let paid = points.min(self.score);
+        self.score -= paid;
+        self.stats.penalty_points += paid;
+    }
+
+    fn add_popup(&mut self, text: String, color: Color, row: usize) {
+        self.popups.push(Popup {
+            text,
+            color,
+            row: row.min(ROWS - 1),
+            spawned: self.clock,
+        });
+    }
+
     fn apply_gravity(&mut self) {
-        for _ in 0..HEIGHT {
-            for y in (0..HEIGHT - 1).rev() {
-                for x in 0..WIDTH {
-                    if self.board[y][x].is_some() && self.board[y + 1][x].is_none() {
-                        self.board[y + 1][x] = self.board[y][x];
-                        self.board[y][x] = None;
-                    }
+        let width = self.width();
+        if self.tilt != 0 {
+            for row in &mut self.board[BUFFER_ROWS..] {
+                let cells: Vec<Option<CellType>> = row.drain(..).filter(Option::is_some).collect();
+                let gap = vec![None; width - cells.len()];
+                if self.tilt < 0 {
+                    row.extend(cells.into_iter().chain(gap));
+                } else {
+                    row.extend(gap.into_iter().chain(cells));
+                }
+            }
+        }
+        for x in 0..width {
+            let mut floor = ROWS;
+            for y in (0..ROWS).rev() {
+                if let Some(cell) = self.board[y][x].take() {
+                    floor -= 1;
+                    self.board[floor][x] = Some(cell);
                 }
             }
         }
     }
 
+    fn spawn_room(&self) -> u32 {
+        match self.config.safe_rows {
+            0 => u32::MAX,
+            safe => self.stack_top().unwrap_or(HEIGHT).saturating_sub(safe) as u32,
+        }
+    }
+
     fn spawn_obstacle(&mut self) {
-        let mut rng = rand::rng();
-        let x = rng.random_range(0..WIDTH);
-        let y = HEIGHT - 1;
+        if self.spawn_room() == 0 {
+            return;
+        }
+        let x = self.rng.random_range(0..self.width());
+        let y = ROWS - 1;
 
         if self.board[y][x].is_none() {
             self.board[y][x] = Some(CellType::Obstacle);
@@ -422,338 +1607,3327 @@ impl Game {
     }
 
     fn spawn_power_up(&mut self) {
-        let mut rng = rand::rng();
-        let x = rng.random_range(0..WIDTH);
-        let y = HEIGHT - 1;
+        self.last_powerup_time = self.clock;
+        let filled: Vec<(usize, usize)> = (BUFFER_ROWS..ROWS)
+            .flat_map(|y| (0..self.width()).map(move |x| (x, y)))
+            .filter(|&(x, y)| matches!(self.board[y][x], Some(CellType::Normal(..))))
+            .collect();
+        let embed = self.config.power_ups_in_stack && !filled.is_empty();
+        let (x, y) = if embed {
+            filled[self.rng.random_range(0..filled.len())]
+        } else {
+            (self.rng.random_range(0..self.width()), ROWS - 1)
+        };
 
-        if self.board[y][x].is_none() {
+        if embed || self.board[y][x].is_none() {
             let powerups = [
                 PowerUpType::Bomb,
                 PowerUpType::SlowTime,
                 PowerUpType::Ghost,
                 PowerUpType::Hammer,
                 PowerUpType::Random,
+                PowerUpType::Well,
+                PowerUpType::Tilt,
             ];
-            self.board[y][x] = Some(CellType::PowerUp(powerups[rng.random_range(0..5)]));
+            self.board[y][x] = Some(CellType::PowerUp(powerups[self.rng.random_range(0..7)]));
         }
     }
 
     fn spawn_new_piece(&mut self) {
-        self.current = self.next.clone();
-        self.current_x = (WIDTH / 2 - 2) as i32;
+        if let Some(typ) = self.forced_next.take() {
+            self.current = Tetromino::new(typ);
+        } else {
+            let typ = self.pieces.next();
+            self.next.push_back(Tetromino::new(typ));
+            self.current = self.next.pop_front().unwrap();
+        }
+        self.enter_field();
+    }
+
+    fn enter_field(&mut self) {
+        self.current_x = self.current.spawn_x(self.width());
         self.current_y = 0;
+        self.piece_spawned_at = self.clock;
+        self.lock_at = None;
+        self.grounded_at = None;
+        self.lowest_y = 0;
+        if self.can_move(0, 0) {
+            self.move_piece(0, 1);
+            if self.charge != 0 {
+                while self.move_piece(self.charge, 0) {}
+            }
+            self.track_ground();
+        } else {
+            self.top_out();
+        }
+    }
 
-        let mut rng = rand::rng();
-        let types = [
-            TetrominoType::I,
-            TetrominoType::O,
-            TetrominoType::T,
-            TetrominoType::S,
-            TetrominoType::Z,
-            TetrominoType::J,
-            TetrominoType::L,
-        ];
-        self.next = Tetromino::new(types[rng.random_range(0..7)]);
+    fn play_note(&self, frequency: u32, degree: i32, duration_ms: u64) {
+        if self.config.music {
+            play_sound(self.config.tuning.frequency(degree), duration_ms);
+        } else {
+            play_sound(frequency, duration_ms);
+        }
+    }
 
-        if !self.can_move(0, 0) {
-            self.game_over = true;
-            play_sound(200, 100);
-            play_sound(150, 100);
-            play_sound(100, 200);
+    fn top_out(&mut self) {
+        self.game_over = true;
+        play_sound(200, 100);
+        play_sound(150, 100);
+        play_sound(100, 200);
+    }
+
+    fn apply(&mut self, action: Action) {
+        match action {
+            Action::Left | Action::Right => {
+                let dx = if (action == Action::Left) != self.config.mirror {
+                    -1
+                } else {
+                    1
+                };
+                if self.move_piece(dx, 0) && self.config.music {
+                    play_sound(self.config.tuning.frequency(self.current_x), 20);
+                }
+            }
+            Action::SoftDrop => self.press_soft_drop(),
+            Action::Rotate => self.rotate_piece(),
+            Action::HardDrop => self.press_hard_drop(),
+            Action::Hold => self.hold_piece(),
         }
     }
 
-    fn drop_piece(&mut self) {
-        while self.move_piece(0, 1) {}
-        play_sound(600, 80);
-        self.lock_piece();
+    fn garbage_rows(&self) -> u32 {
+        self.board
+            .iter()
+            .filter(|row| {
+                row.iter()
+                    .any(|cell| matches!(cell, Some(CellType::Garbage)))
+            })
+            .count() as u32
     }
 
-    fn use_hammer(&mut self, line: usize) {
-        if line < HEIGHT {
-            self.board.remove(line);
-            self.board.insert(0, vec![None; WIDTH]);
-            self.hammer_mode = false;
-            self.score += 50;
-            play_sound(400, 100);
-            self.apply_gravity();
+    fn deal_cheese(&mut self) {
+        let rows = CHEESE_ROWS
+            .saturating_sub(self.garbage_rows())
+            .min(self.cheese_pending)
+            .min(self.spawn_room());
+        self.cheese_pending -= rows;
+        self.rise_garbage(rows);
+
+        if self.cheese_pending == 0 && self.garbage_rows() == 0 {
+            self.game_over = true;
+            self.won = true;
+            play_sound(523, 100);
+            play_sound(659, 100);
+            play_sound(784, 200);
         }
     }
 
-    fn get_fall_speed(&self) -> Duration {
-        let base_speed = 500;
-        let speed = if self.slow_time_active {
-            base_speed * 2
-        } else {
-            base_speed
-        };
-        Duration::from_millis(speed)
+    fn receive_garbage(&mut self, lines: u32) {
+        if lines == 0 {
+            return;
+        }
+        self.pending_garbage += lines;
+        self.garbage_due.get_or_insert(self.clock + GARBAGE_DELAY);
     }
-}
 
-fn play_sound(_frequency: u32, duration_ms: u64) {
-    print!("\x07");
-    io::stdout().flush().unwrap();
-    std::thread::sleep(Duration::from_millis(duration_ms / 10));
-}
+    fn rise_garbage(&mut self, lines: u32) {
+        for _ in 0..lines.min(self.spawn_room()) {
+            if self.board[0].iter().any(|cell| cell.is_some()) {
+                self.top_out();
+                return;
+            }
+            self.board.remove(0);
+            let width = self.width();
+            let gap = match self.garbage_gap {
+                Some(gap) if self.config.garbage_clean && gap < width => gap,
+                _ => self.rng.random_range(0..width),
+            };
+            self.garbage_gap = Some(gap);
+            self.board.push(
+                (0..width)
+                    .map(|x| (x != gap).then_some(CellType::Garbage))
+                    .collect(),
+            );
+        }
 
-fn draw(stdout: &mut io::Stdout, game: &Game) -> io::Result<()> {
-    queue!(stdout, cursor::MoveTo(0, 0))?;
+        while !self.can_move(0, 0) {
+            if self.current_y == 0 {
+                self.top_out();
+                return;
+            }
+            self.current_y -= 1;
+        }
+    }
 
-    queue!(
-        stdout,
-        SetForegroundColor(Color::White),
-        Print("╔"),
-        Print("═".repeat(WIDTH * 2)),
-        Print("╗\n\r")
-    )?;
-    queue!(
-        stdout,
-        Print("║"),
-        SetForegroundColor(Color::Red),
-        Print(format!(
-            "{:^width$}",
-            "⚡ TETRIS CHAOS ⚡",
-            width = WIDTH * 2
-        )),
-        SetForegroundColor(Color::White),
-        Print("║\n\r")
-    )?;
-    queue!(
-        stdout,
-        Print("╠"),
-        Print("═".repeat(WIDTH * 2)),
-        Print("╣\n\r")
-    )?;
+    fn ghost_drop_y(&self) -> i32 {
+        let mut dy = 0;
+        while self.can_move(0, dy + 1) {
+            dy += 1;
+        }
+        let mut y = self.current_y + dy;
+        if self.ghost_mode && self.config.ghost_solid_lock {
+            while self.overlaps_solid(y) {
+                y -= 1;
+            }
+        }
+        y
+    }
 
-    for y in 0..HEIGHT {
-        queue!(stdout, SetForegroundColor(Color::White), Print("║"))?;
+    fn drop_distance(&self) -> Option<i32> {
+        (self.config.drop_distance && !self.game_over).then(|| self.ghost_drop_y() - self.current_y)
+    }
 
-        if game.hammer_mode {
-            queue!(stdout, SetForegroundColor(Color::DarkYellow), Print(""))?;
+    fn hold_ghost(&self) -> Option<(Tetromino, i32)> {
+        if !(self.config.practice && self.config.ghost_show_hold) {
+            return None;
         }
+        let piece = Tetromino::new(self.hold?);
+        if !self.fits(&piece, self.current_x, self.current_y) {
+            return None;
+        }
+        let mut y = self.current_y;
+        while self.fits(&piece, self.current_x, y + 1) {
+            y += 1;
+        }
+        Some((piece, y))
+    }
 
-        for x in 0..WIDTH {
-            let mut drawn = false;
+    fn swap_preview(&self) -> Option<(Tetromino, i32, i32)> {
+        if !self.hold_preview || self.config.no_hold || !self.can_hold {
+            return None;
+        }
+        let typ = self.hold.unwrap_or(self.next.front()?.typ);
+        let piece = Tetromino::new(typ);
+        let x = piece.spawn_x(self.width());
+        let y = if self.fits(&piece, x, 1) { 1 } else { 0 };
+        Some((piece, x, y))
+    }
 
-            for (i, row) in game.current.shape.iter().enumerate() {
-                for (j, &cell) in row.iter().enumerate() {
-                    if cell {
-                        let px = game.current_x + j as i32;
-                        let py = game.current_y + i as i32;
-                        if px == x as i32 && py == y as i32 {
-                            let color = if game.ghost_mode {
-                                Color::DarkCyan
-                            } else {
-                                game.current.color
-                            };
-                            queue!(stdout, SetForegroundColor(color), Print(BLOCK))?;
-                            drawn = true;
-                        }
-                    }
-                }
+    fn soft_drop(&mut self) {
+        if self.move_piece(0, 1) {
+            self.lock_at = None;
+            if self.soft_dropping {
+                self.award(ScoreSource::Drop, self.config.scoring.soft_drop);
             }
+        } else if self.lock_at.is_none() || self.lock_timed_out() {
+            self.lock_piece();
+        }
+    }
 
-            if !drawn {
-                match &game.board[y][x] {
-                    Some(CellType::Normal(color)) => {
-                        queue!(stdout, SetForegroundColor(*color), Print(BLOCK))?;
-                    }
-                    Some(CellType::Obstacle) => {
-                        queue!(stdout, SetForegroundColor(Color::DarkGrey), Print("▓▓"))?;
-                    }
-                    Some(CellType::PowerUp(powerup)) => {
-                        let (symbol, color) = match powerup {
-                            PowerUpType::Bomb => ("💣", Color::Red),
-                            PowerUpType::SlowTime => ("⏰", Color::Cyan),
-                            PowerUpType::Ghost => ("👻", Color::White),
-                            PowerUpType::Hammer => ("🔨", Color::Yellow),
-                            PowerUpType::Random => ("🎲", Color::Magenta),
-                        };
-                        queue!(stdout, SetForegroundColor(color), Print(symbol))?;
-                    }
-                    None => {
-                        queue!(stdout, Print("  "))?;
-                    }
-                }
-            }
+    fn press_soft_drop(&mut self) {
+        if !self.soft_dropping {
+            self.soft_dropping = true;
+            self.fall_accumulator = Duration::ZERO;
+            self.soft_drop();
         }
+        self.soft_drop_until = self.soft_drop_timeout.map(|timeout| self.clock + timeout);
+    }
 
-        queue!(stdout, SetForegroundColor(Color::White), Print("║"))?;
+    fn peek(&mut self, on: bool) {
+        self.peeking = on;
+        self.peek_until = self
+            .soft_drop_timeout
+            .filter(|_| on)
+            .map(|_| self.clock + PEEK_HOLD);
+    }
 
-        match y {
-            1 => queue!(
-                stdout,
-                Print("  Score: "),
-                SetForegroundColor(Color::Yellow),
-                Print(format!("{}", game.score))
-            )?,
-            2 => {
-                if game.combo > 0 {
-                    queue!(
-                        stdout,
-                        SetForegroundColor(Color::Red),
-                        Print(format!("  COMBO x{}", game.combo + 1))
-                    )?;
-                }
-            }
-            4 => queue!(
-                stdout,
-                SetForegroundColor(Color::White),
-                Print("  Suivant:")
-            )?,
-            5..=8 => {
-                let ny = y - 5;
-                queue!(stdout, Print("  "))?;
-                for j in 0..4 {
-                    if ny < game.next.shape.len()
-                        && j < game.next.shape[ny].len()
-                        && game.next.shape[ny][j]
+    fn release_soft_drop(&mut self) {
+        self.soft_dropping = false;
+        self.soft_drop_until = None;
+    }
+
+    fn press_hard_drop(&mut self) {
+        let window = self.config.double_tap_drop;
+        if window.is_zero() {
+            return self.drop_piece();
+        }
+        match self.drop_tapped_at.take() {
+            Some(at) if self.clock - at <= window => self.drop_piece(),
+            _ => self.drop_tapped_at = Some(self.clock),
+        }
+    }
+
+    fn drop_piece(&mut self) {
+        if self.lock_at.is_some() {
+            self.lock_piece();
+            return;
+        }
+        let start_y = self.current_y;
+        while self.move_piece(0, 1) {}
+        let distance = (self.current_y - start_y) as u32;
+        self.award(ScoreSource::Drop, distance * self.config.scoring.hard_drop);
+
+        let mut cells = Vec::new();
+        for (i, row) in self.current.shape.iter().enumerate() {
+            for (j, &cell) in row.iter().enumerate() {
+                let x = (self.current_x + j as i32) as usize;
+                for y in start_y + i as i32..self.current_y + i as i32 {
+                    if cell
+                        && y >= 0
+                        && !self
+                            .current
+                            .covers(self.current_x, self.current_y, x, y as usize)
                     {
-                        queue!(stdout, SetForegroundColor(game.next.color), Print(BLOCK))?;
-                    } else {
-                        queue!(stdout, Print("  "))?;
+                        cells.push((x, y as usize));
                     }
                 }
             }
-            10 => queue!(
-                stdout,
-                SetForegroundColor(Color::Cyan),
-                Print("  Power-ups:")
-            )?,
-            11 => {
-                if game.ghost_mode {
-                    queue!(
-                        stdout,
-                        SetForegroundColor(Color::White),
-                        Print(format!("  👻 Ghost x{}", game.ghost_remaining))
-                    )?;
-                }
-            }
-            12 => {
-                if game.slow_time_active {
-                    queue!(
-                        stdout,
-                        SetForegroundColor(Color::Cyan),
-                        Print("  ⏰ Slow Time")
-                    )?;
-                }
-            }
-            13 => {
-                if game.hammer_mode {
-                    queue!(
-                        stdout,
-                        SetForegroundColor(Color::Yellow),
-                        Print("  🔨 Hammer: 1-9")
-                    )?;
-                }
-            }
-            15 => queue!(
-                stdout,
-                SetForegroundColor(Color::White),
-                Print("  Contrôles:")
-            )?,
-            16 => queue!(stdout, Print("  ←→↑↓ Jouer"))?,
-            17 => queue!(stdout, Print("  Space: Drop"))?,
-            18 => queue!(stdout, Print("  Q: Quitter"))?,
-            _ => {}
         }
+        self.trails.push(Trail {
+            cells,
+            color: self.current.color,
+            spawned: self.clock,
+        });
+        self.play_note(600, 0, 80);
+        let grounded_at = *self.grounded_at.get_or_insert(self.clock);
+        let mut lock_at = self.clock + self.config.lock_delay;
+        if !self.config.lock_timeout.is_zero() {
+            lock_at = lock_at.min(grounded_at + self.config.lock_timeout);
+        }
+        if self.config.lock_delay_on_drop && lock_at > self.clock {
+            self.lock_at = Some(lock_at);
+        } else {
+            self.lock_piece();
+        }
+    }
 
-        queue!(stdout, Print("\n\r"))?;
+    fn use_hammer(&mut self, line: usize) {
+        if line < ROWS {
+            self.board.remove(line);
+            self.board.insert(0, vec![None; self.width()]);
+            self.hammer_mode = false;
+            self.award(ScoreSource::Hammer, self.config.scoring.hammer);
+            self.play_note(400, 5, 100);
+            self.apply_gravity();
+        }
     }
 
-    queue!(
-        stdout,
-        SetForegroundColor(Color::White),
-        Print("╚"),
-        Print("═".repeat(WIDTH * 2)),
-        Print("╝\n\r")
-    )?;
+    fn cancel_hammer(&mut self) {
+        if !self.hammer_mode {
+            return;
+        }
+        self.hammer_mode = false;
+        if self.config.keep_cancelled_hammer {
+            self.hammers += 1;
+        }
+        play_sound(250, 40);
+    }
 
-    if game.game_over {
-        queue!(
-            stdout,
-            SetForegroundColor(Color::Red),
-            Print("\n\r💀 GAME OVER 💀 Score: "),
-            Print(format!("{}", game.score)),
-            Print("\n\r")
-        )?;
+    fn arm_hammer(&mut self) {
+        if self.hammer_mode || self.hammers == 0 {
+            return;
+        }
+        self.hammers -= 1;
+        self.hammer_mode = true;
+        self.hammer_armed_at = self.clock;
+        play_sound(400, 40);
     }
 
-    stdout.flush()?;
-    Ok(())
-}
+    fn load_scenario(&mut self) {
+        let Some(rows) = self.config.scenario.and_then(|n| SCENARIOS.get(n - 1)) else {
+            return;
+        };
+        self.board = vec![vec![None; self.width()]; ROWS];
+        for (y, row) in (ROWS - rows.len()..).zip(rows.iter()) {
+            for (x, c) in row.chars().enumerate().take(self.width()) {
+                if c == '#' {
+                    self.board[y][x] = Some(CellType::Garbage);
+                }
+            }
+        }
+        self.current = Tetromino::new(TetrominoType::T);
+        self.enter_field();
+        self.combo = 0;
+        self.last_clear_time = None;
+    }
 
-fn main() -> io::Result<()> {
-    let mut stdout = io::stdout();
-    terminal::enable_raw_mode()?;
-    execute!(stdout, terminal::Clear(ClearType::All), cursor::Hide)?;
+    fn soft_reset(&mut self) {
+        self.board = vec![vec![None; self.width()]; ROWS];
+        self.penalize(RESET_PENALTY);
+        self.combo = 0;
+        self.last_clear_time = None;
+        self.danger = false;
+        self.add_popup(format!("-{RESET_PENALTY}"), Color::Red, ROWS / 2);
+        play_sound(150, 150);
+    }
+
+    fn update(&mut self, dt: Duration) {
+        self.clock += dt;
+        if self.config.mode.pace_key().is_some() && !self.game_over {
+            let second = self.clock.as_secs() as usize;
+            self.pace_log.resize(second + 1, self.lines_cleared_total);
+        }
 
-    let mut game = Game::new();
-    let mut last_fall = Instant::now();
+        if std::mem::take(&mut self.hold_pending) && self.can_hold {
+            self.swap_hold();
+        }
 
-    loop {
-        draw(&mut stdout, &game)?;
+        if self.slow_time_active
+            && let Some(end_time) = self.slow_time_end
+            && self.clock >= end_time
+        {
+            self.slow_time_active = false;
+            self.slow_time_stacks = 0;
+            self.slow_time_end = None;
+        }
 
-        if game.game_over {
-            terminal::disable_raw_mode()?;
-            execute!(stdout, cursor::Show)?;
-            break;
+        if let Some(end) = self.well_until
+            && self.clock >= end
+            && self.resize_well(WIDTH)
+        {
+            self.well_until = None;
         }
 
-        if game.slow_time_active {
-            if let Some(end_time) = game.slow_time_end {
-                if Instant::now() >= end_time {
-                    game.slow_time_active = false;
-                    game.slow_time_end = None;
-                }
+        if let Some(end) = self.ghost_end
+            && self.clock >= end
+        {
+            self.ghost_mode = false;
+            if self.can_move(0, 0) {
+                self.ghost_end = None;
+            } else {
+                self.ghost_mode = true;
             }
         }
 
-        if event::poll(Duration::from_millis(50))? {
-            if let Event::Key(key) = event::read()? {
-                match key.code {
-                    KeyCode::Left => {
-                        game.move_piece(-1, 0);
-                    }
-                    KeyCode::Right => {
-                        game.move_piece(1, 0);
-                    }
-                    KeyCode::Down => {
-                        if !game.move_piece(0, 1) {
-                            game.lock_piece();
-                        }
-                    }
-                    KeyCode::Up => {
-                        game.rotate_piece();
-                    }
-                    KeyCode::Char(' ') => {
-                        game.drop_piece();
-                    }
-                    KeyCode::Char('q') | KeyCode::Char('Q') => break,
+        if let Some(until) = self.soft_drop_until
+            && self.clock >= until
+        {
+            self.release_soft_drop();
+        }
 
-                    KeyCode::Char(c) if game.hammer_mode && c.is_digit(10) => {
-                        if let Some(digit) = c.to_digit(10) {
-                            if digit > 0 && digit <= HEIGHT as u32 {
-                                game.use_hammer(HEIGHT - digit as usize);
-                            }
-                        }
-                    }
-                    _ => {}
-                }
-            }
+        if let Some(until) = self.peek_until
+            && self.clock >= until
+        {
+            self.peek(false);
         }
 
-        let fall_speed = game.get_fall_speed();
-        if last_fall.elapsed() >= fall_speed {
-            if !game.move_piece(0, 1) {
-                game.lock_piece();
+        if let Some(lock_at) = self.lock_at
+            && self.clock >= lock_at
+        {
+            self.lock_at = None;
+            if !self.can_move(0, 1) {
+                self.lock_piece();
             }
-            last_fall = Instant::now();
+        }
+
+        if !self.game_over && self.lock_timed_out() && !self.can_move(0, 1) {
+            self.lock_piece();
+        }
+
+        if let Some(due) = self.garbage_due
+            && self.clock >= due
+        {
+            self.garbage_due = None;
+            let lines = std::mem::take(&mut self.pending_garbage);
+            self.rise_garbage(lines);
+        }
+
+        if self.config.mode == GameMode::Survival
+            && !self.game_over
+            && self.clock >= self.survival_rise_at
+        {
+            self.rise_garbage(1);
+            self.survival_rise_at = self.clock + self.survival_interval();
+        }
+
+        let every = self.config.power_up_every;
+        if !every.is_zero() && !self.game_over && self.clock - self.last_powerup_time >= every {
+            self.spawn_power_up();
+        }
+
+        let clock = self.clock;
+        self.popups
+            .retain(|popup| clock - popup.spawned < POPUP_TTL);
+        self.trails
+            .retain(|trail| clock - trail.spawned < TRAIL_TTL);
+        self.bursts
+            .retain(|burst| clock - burst.spawned < BURST_TTL);
+        self.hold_swap
+            .take_if(|swap| clock - swap.spawned >= SWAP_TTL);
+
+        self.fall_accumulator += dt;
+        while !self.game_over && self.fall_accumulator >= self.get_fall_speed() {
+            self.fall_accumulator -= self.get_fall_speed();
+            self.soft_drop();
         }
     }
 
-    terminal::disable_raw_mode()?;
-    execute!(stdout, cursor::Show)?;
-    Ok(())
+    fn survival_interval(&self) -> Duration {
+        let ramp = 0.5f64.powf(self.clock.as_secs_f64() / SURVIVAL_HALF_LIFE);
+        SURVIVAL_START.mul_f64(ramp).max(SURVIVAL_MIN)
+    }
+
+    fn level(&self) -> u32 {
+        self.config.start_level + self.lines_cleared_total / 10
+    }
+
+    fn stack_top(&self) -> Option<usize> {
+        self.board
+            .iter()
+            .position(|row| row.iter().any(|cell| cell.is_some()))
+            .map(|y| y.saturating_sub(BUFFER_ROWS))
+    }
+
+    fn state_hash(&self) -> u64 {
+        let mut bytes = Vec::new();
+        for cell in self.board.iter().flatten() {
+            bytes.push(match cell {
+                None => 0,
+                Some(CellType::Normal(..)) => 1,
+                Some(CellType::Obstacle) => 2,
+                Some(CellType::Garbage) => 3,
+                Some(CellType::PowerUp(PowerUpType::Bomb)) => 4,
+                Some(CellType::PowerUp(PowerUpType::SlowTime)) => 5,
+                Some(CellType::PowerUp(PowerUpType::Ghost)) => 6,
+                Some(CellType::PowerUp(PowerUpType::Hammer)) => 7,
+                Some(CellType::PowerUp(PowerUpType::Random)) => 8,
+                Some(CellType::PowerUp(PowerUpType::Well)) => 9,
+                Some(CellType::PowerUp(PowerUpType::Tilt)) => 10,
+            });
+        }
+        bytes.extend(self.score.to_le_bytes());
+        bytes.extend(self.lines_cleared_total.to_le_bytes());
+        bytes.extend(self.current_x.to_le_bytes());
+        bytes.extend(self.current_y.to_le_bytes());
+        bytes.push(self.current.typ as u8);
+        bytes.push(self.current.rotation as u8);
+        bytes.extend(self.next.iter().map(|piece| piece.typ as u8));
+        bytes.push(self.hold.map_or(u8::MAX, |typ| typ as u8));
+
+        bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        })
+    }
+
+    fn blink(&self, period: Duration) -> bool {
+        self.config.flashing && (self.clock.as_millis() / period.as_millis()) % 2 == 1
+    }
+
+    fn in_danger(&self) -> bool {
+        self.stack_top().is_some_and(|top| top < DANGER_ROWS)
+    }
+
+    fn bullet_time(&self) -> bool {
+        self.config.bullet_time && self.stack_top().is_some_and(|top| top <= BULLET_TIME_ROWS)
+    }
+
+    fn get_fall_speed(&self) -> Duration {
+        let base_speed = self
+            .config
+            .speed_curve
+            .fall_ms(self.level(), self.config.fall_ms, self.config.level_factor)
+            .max(MIN_FALL_MS);
+        let mut speed = if self.slow_time_active {
+            base_speed * (1 + self.slow_time_stacks as u64)
+        } else {
+            base_speed
+        };
+        if self.bullet_time() {
+            speed *= 3;
+        }
+        if self.soft_dropping {
+            (Duration::from_millis(speed) / self.config.soft_drop_factor)
+                .max(Duration::from_millis(1))
+        } else {
+            Duration::from_millis(speed)
+        }
+    }
+}
+
+struct GameRng(ChaCha12Rng);
+
+impl Clone for GameRng {
+    fn clone(&self) -> Self {
+        let mut rng = ChaCha12Rng::from_seed(self.0.get_seed());
+        rng.set_stream(self.0.get_stream());
+        rng.set_word_pos(self.0.get_word_pos());
+        GameRng(rng)
+    }
+}
+
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.0.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dst: &mut [u8]) {
+        self.0.fill_bytes(dst)
+    }
+}
+
+#[derive(Clone)]
+struct Randomizer {
+    rng: GameRng,
+    bag: Vec<TetrominoType>,
+    size: usize,
+}
+
+impl Randomizer {
+    fn new(seed: u64, size: usize) -> Self {
+        Randomizer {
+            rng: GameRng(ChaCha12Rng::seed_from_u64(seed ^ PIECE_SEED_SALT)),
+            bag: Vec::new(),
+            size,
+        }
+    }
+
+    fn next(&mut self) -> TetrominoType {
+        if self.size == 0 {
+            return TetrominoType::ALL[self.rng.random_range(0..7)];
+        }
+        if self.bag.is_empty() {
+            for _ in 0..self.size / 7 {
+                self.bag.extend(TetrominoType::ALL);
+            }
+            self.bag.shuffle(&mut self.rng);
+        }
+        self.bag.pop().unwrap()
+    }
+}
+
+struct KeyRepeat {
+    reported: bool,
+    last_key: Option<(KeyCode, Instant)>,
+    accepted: Vec<(usize, Action, Instant)>,
+    charged: Vec<(usize, i32, Instant)>,
+}
+
+impl KeyRepeat {
+    fn new(key_releases: bool) -> Self {
+        KeyRepeat {
+            reported: key_releases,
+            last_key: None,
+            accepted: Vec::new(),
+            charged: Vec::new(),
+        }
+    }
+
+    fn expire(&mut self) -> Vec<Input> {
+        let now = Instant::now();
+        let mut released = Vec::new();
+        self.charged.retain(|&(player, _, at)| {
+            let held = now - at < REPEAT_GAP;
+            if !held {
+                released.push(Input::Charge(player, 0));
+            }
+            held
+        });
+        released
+    }
+
+    fn charge(&mut self, player: usize, dir: i32, repeat: bool, now: Instant) -> Option<Input> {
+        let charged = self.charged.iter().position(|&(p, _, _)| p == player);
+        match (charged, repeat) {
+            (Some(i), true) => {
+                let was = std::mem::replace(&mut self.charged[i], (player, dir, now)).1;
+                (was != dir).then_some(Input::Charge(player, dir))
+            }
+            (None, true) => {
+                self.charged.push((player, dir, now));
+                Some(Input::Charge(player, dir))
+            }
+            (Some(i), false) => {
+                self.charged.remove(i);
+                Some(Input::Charge(player, 0))
+            }
+            (None, false) => None,
+        }
+    }
+
+    fn filter(&mut self, config: &Config, key: &KeyEvent, inputs: Vec<Input>) -> Vec<Input> {
+        let now = Instant::now();
+        let repeat = if self.reported {
+            key.kind == KeyEventKind::Repeat
+        } else {
+            self.last_key
+                .is_some_and(|(code, at)| code == key.code && now - at < REPEAT_GAP)
+        };
+        self.last_key = Some((key.code, now));
+
+        let mut kept = Vec::new();
+        for input in inputs {
+            let Input::Action(player, action) = input else {
+                kept.push(input);
+                continue;
+            };
+            if config.das_carry && matches!(action, Action::Left | Action::Right) {
+                let dir = if action == Action::Left { -1 } else { 1 };
+                kept.extend(self.charge(player, dir, repeat, now));
+            }
+            let setting = match action {
+                Action::Left | Action::Right => config.move_repeat,
+                Action::SoftDrop => config.soft_drop_repeat,
+                _ => {
+                    kept.push(input);
+                    continue;
+                }
+            };
+            let last = self
+                .accepted
+                .iter()
+                .position(|&(p, a, _)| p == player && a == action);
+            let allowed = match setting {
+                _ if !repeat => true,
+                None => false,
+                Some(interval) => last.is_none_or(|i| now - self.accepted[i].2 >= interval),
+            };
+            if !allowed {
+                continue;
+            }
+            if let Some(i) = last {
+                self.accepted.remove(i);
+            }
+            self.accepted.push((player, action, now));
+            kept.push(input);
+            if action == Action::SoftDrop && setting.is_none() {
+                kept.push(Input::Release(player));
+            }
+        }
+        kept
+    }
+}
+
+fn darken(color: Color) -> Color {
+    match color {
+        Color::Red => Color::DarkRed,
+        Color::Green => Color::DarkGreen,
+        Color::Yellow => Color::DarkYellow,
+        Color::Blue => Color::DarkBlue,
+        Color::Magenta => Color::DarkMagenta,
+        Color::Cyan => Color::DarkCyan,
+        Color::White => Color::Grey,
+        _ => Color::DarkGrey,
+    }
+}
+
+fn rainbow(clock: Duration, offset: usize) -> Color {
+    let turn = clock.as_secs_f64() / RAINBOW_PERIOD.as_secs_f64()
+        + offset as f64 / (WIDTH + HEIGHT) as f64;
+    let hue = turn.fract() * 6.0;
+    let rise = (hue.fract() * 255.0) as u8;
+    let (r, g, b) = match hue as u8 {
+        0 => (255, rise, 0),
+        1 => (255 - rise, 255, 0),
+        2 => (0, 255, rise),
+        3 => (0, 255 - rise, 255),
+        4 => (rise, 0, 255),
+        _ => (255, 0, 255 - rise),
+    };
+    Color::Rgb { r, g, b }
+}
+
+fn effect_bar(share: f64) -> String {
+    let filled = ((share * 5.0).ceil() as usize).min(5);
+    format!("{}{}", "▰".repeat(filled), "▱".repeat(5 - filled))
+}
+
+fn preview_row(
+    piece: Option<&Tetromino>,
+    color: Option<Color>,
+    theme: Theme,
+    row: usize,
+    block: &str,
+    blank: &str,
+) -> Vec<Segment> {
+    (0..4)
+        .map(|j| match piece {
+            Some(piece)
+                if piece
+                    .shape
+                    .get(row)
+                    .is_some_and(|r| r.get(j) == Some(&true)) =>
+            {
+                (color.unwrap_or(theme.tint(piece.color)), block.to_string())
+            }
+            _ => (Color::White, blank.to_string()),
+        })
+        .collect()
+}
+
+fn side_panel(game: &Game, keys: &KeyConfig) -> Panel {
+    let block = game.config.block.as_str();
+    let cell_width = game.config.cell_width();
+    let depth = game.config.preview_depth;
+    let blank = " ".repeat(cell_width);
+    let mut panel = Panel::new(panel_width(&game.config), HEIGHT);
+
+    let mut score = vec![
+        (Color::White, "Score: ".to_string()),
+        (Color::Yellow, game.score.to_string()),
+    ];
+    match game.config.mode {
+        GameMode::Cheese(_) => score.push((
+            Color::DarkGrey,
+            format!("  Déchets: {}", game.garbage_rows() + game.cheese_pending),
+        )),
+        GameMode::Survival => {
+            let secs = game.clock.as_secs();
+            score.push((
+                Color::DarkGrey,
+                format!("  Temps: {}:{:02}", secs / 60, secs % 60),
+            ));
+        }
+        GameMode::PerfectClear => score.push((
+            Color::DarkGrey,
+            format!("  PC: {}", game.stats.perfect_clears),
+        )),
+        _ => {}
+    }
+    if let Some(rows) = game.drop_distance() {
+        score.push((Color::DarkGrey, format!("  ↓{rows}")));
+    }
+    panel.line(score);
+    if let Some(goal) = game.goal_line() {
+        panel.line(goal);
+    }
+    if game.combo > 0 {
+        panel.text(Color::Red, format!("COMBO x{}", game.combo + 1));
+    } else if let Some(delta) = game.pace_delta() {
+        let (color, text) = match delta {
+            0 => (Color::White, "à égalité".to_string()),
+            1.. => (Color::Green, format!("{delta:+} lignes")),
+            _ => (Color::Red, format!("{delta:+} lignes")),
+        };
+        panel.text(color, format!("Record: {text}"));
+    } else {
+        panel.blank();
+    }
+
+    if game.config.chaos {
+        let filled = (game.chaos * 10.0).round() as usize;
+        panel.text(
+            Color::Magenta,
+            format!("Chaos: {}{}", "▰".repeat(filled), "▱".repeat(10 - filled)),
+        );
+    }
+    let mut header = vec![(
+        Color::White,
+        format!(
+            "{:<width$}  {}",
+            if depth > 0 { "Suivant:" } else { "" },
+            if game.config.no_hold { "" } else { "Réserve:" },
+            width = 4 * cell_width
+        ),
+    )];
+    let cooldown = game.hold_cooldown();
+    if !cooldown.is_zero() {
+        header.push((Color::DarkGrey, format!(" {:.1}s", cooldown.as_secs_f64())));
+    }
+    panel.line(header);
+
+    let forced = game.forced_next.map(Tetromino::new);
+    let queued: Vec<&Tetromino> = forced.iter().chain(&game.next).take(depth).collect();
+    let hold = game.hold.map(Tetromino::new);
+    let hold_color = if game.swap_flash() {
+        Some(Color::White)
+    } else if game.can_hold && game.hold_cooldown().is_zero() {
+        None
+    } else {
+        Some(Color::DarkGrey)
+    };
+    let mini = &block[..block.chars().next().map_or(0, char::len_utf8)];
+    let theme = game.theme();
+    for row in 0..4 {
+        let mut line = preview_row(queued.first().copied(), None, theme, row, block, &blank);
+        line.push((Color::White, "  ".to_string()));
+        line.extend(preview_row(
+            hold.as_ref(),
+            hold_color,
+            theme,
+            row,
+            block,
+            &blank,
+        ));
+
+        for pair in queued.get(1..).unwrap_or_default().chunks(2) {
+            let piece = pair.get(row / 2).copied();
+            let rows: Vec<usize> = piece
+                .map(|piece| {
+                    (0..piece.shape.len())
+                        .filter(|&i| piece.shape[i].contains(&true))
+                        .collect()
+                })
+                .unwrap_or_default();
+            line.push((Color::White, " ".to_string()));
+            match rows.get(row % 2) {
+                Some(&shape_row) => {
+                    line.extend(preview_row(piece, None, theme, shape_row, mini, " "))
+                }
+                None => line.extend(preview_row(None, None, theme, 0, mini, " ")),
+            }
+        }
+        panel.line(line);
+    }
+
+    if game.reset_pending {
+        panel.text(Color::Red, format!("R: vider (-{RESET_PENALTY}) ?"));
+    }
+    panel.section("Power-ups:", Color::Cyan);
+    let timer = |end: Duration, total: Duration| {
+        let left = end.saturating_sub(game.clock).as_secs_f64();
+        let share = left / total.as_secs_f64().max(f64::EPSILON);
+        format!("{} {left:.1}s", effect_bar(share))
+    };
+    let powerups = [
+        game.ghost_mode.then(|| {
+            let left = match game.ghost_end {
+                Some(end) => timer(end, game.config.ghost_duration),
+                None => {
+                    let total = game.config.ghost_pieces.max(game.ghost_remaining).max(1);
+                    let share = game.ghost_remaining as f64 / total as f64;
+                    format!("{} x{}", effect_bar(share), game.ghost_remaining)
+                }
+            };
+            (Color::White, format!("👻 Ghost {left}"))
+        }),
+        game.well_until.map(|end| {
+            (
+                Color::Green,
+                format!("📏 Puits {} {}", game.width(), timer(end, WELL_TIME)),
+            )
+        }),
+        (game.tilt != 0).then(|| {
+            let arrow = if game.tilt < 0 { "←" } else { "→" };
+            let share = game.tilt_remaining as f64 / TILT_PIECES as f64;
+            (
+                Color::Blue,
+                format!(
+                    "🧲 Gravité {arrow} {} x{}",
+                    effect_bar(share),
+                    game.tilt_remaining
+                ),
+            )
+        }),
+        game.slow_time_active.then(|| {
+            let left = timer(game.slow_time_end.unwrap_or(game.clock), SLOW_TIME);
+            let text = match game.slow_time_stacks {
+                0 => format!("⏰ Slow Time {left}"),
+                stacks => format!("⏰ Slow Time x{} {left}", stacks + 1),
+            };
+            (Color::Cyan, text)
+        }),
+        if game.hammer_mode {
+            Some((Color::Yellow, "🔨 Hammer: 1-9 (Esc)".to_string()))
+        } else {
+            (game.hammers > 0).then(|| {
+                (
+                    Color::DarkYellow,
+                    format!("🔨 Hammer x{} ({})", game.hammers, key_name(&keys.hammer)),
+                )
+            })
+        },
+    ];
+    let mut active: Vec<(Color, String)> = powerups.into_iter().flatten().collect();
+    if active.is_empty() {
+        panel.text(Color::DarkGrey, "aucun");
+    }
+    if active.len() > POWERUP_ROWS {
+        let hidden: Vec<String> = active
+            .drain(POWERUP_ROWS - 1..)
+            .filter_map(|(_, text)| text.chars().next())
+            .map(String::from)
+            .collect();
+        active.push((Color::DarkGrey, format!("+ {}", hidden.join(" "))));
+    }
+    for (color, text) in &active {
+        panel.text(*color, text.as_str());
+    }
+    for _ in active.len().max(1)..POWERUP_ROWS {
+        panel.blank();
+    }
+
+    panel.section("Contrôles:", Color::White);
+    let moves =
+        [&keys.left, &keys.right, &keys.rotate, &keys.soft_drop].map(|codes| key_name(codes));
+    let gap = if moves.iter().any(|name| name.contains('/')) {
+        " "
+    } else {
+        ""
+    };
+    panel.text(
+        Color::White,
+        format!(
+            "{} Jouer  {}: Réserve",
+            moves.join(gap),
+            key_name(&keys.hold)
+        ),
+    );
+    panel.text(
+        Color::White,
+        format!("{}: Drop  P: Pause", key_name(&keys.hard_drop)),
+    );
+    if game.config.versus {
+        panel.text(Color::White, "Q: Quitter");
+    } else {
+        panel.text(Color::White, "Q: Quitter  R: Vider");
+    }
+    if game.config.scenario.is_some() {
+        panel.text(Color::White, "1-7: Pièce  T: Scénario");
+    } else if game.config.practice {
+        panel.text(Color::White, "G: Grille  1-7: Pièce");
+    } else if game.config.versus {
+        panel.text(Color::White, "G: Grille");
+    } else {
+        panel.text(Color::White, "G: Grille  Tab: Touches");
+    }
+
+    panel
+}
+
+fn panel_width(config: &Config) -> usize {
+    if !config.layout.side() {
+        return 2;
+    }
+    let mini_columns = config.preview_depth.saturating_sub(1).div_ceil(2);
+    PANEL_WIDTH.max(6 + 8 * config.cell_width() + 5 * mini_columns)
+}
+
+fn results_row(config: &Config) -> u16 {
+    let status = if config.layout.bottom() {
+        STATUS_ROWS
+    } else {
+        0
+    };
+    (5 + HEIGHT) as u16 + status
+}
+
+fn status_bar(game: &Game) -> Panel {
+    let width = game.width() * game.config.cell_width() + 2;
+    let mut bar = Panel::new(width, STATUS_ROWS as usize);
+    let secs = game.clock.as_secs();
+    let mut first = vec![
+        (Color::White, "Score ".to_string()),
+        (Color::Yellow, game.score.to_string()),
+        (Color::White, format!("  Niv {}", game.level())),
+    ];
+    if let Some(rows) = game.drop_distance() {
+        first.push((Color::DarkGrey, format!("  ↓{rows}")));
+    }
+    bar.line(first);
+    bar.text(
+        Color::White,
+        format!(
+            "Lignes {}  {}:{:02}",
+            game.lines_cleared_total,
+            secs / 60,
+            secs % 60
+        ),
+    );
+    let next = game
+        .forced_next
+        .or(game.next.front().map(|piece| piece.typ))
+        .filter(|_| game.config.preview_depth > 0)
+        .map_or('-', TetrominoType::name);
+    let hold = game.hold.map_or('-', TetrominoType::name);
+    bar.text(Color::DarkGrey, format!("Suivant {next}  Réserve {hold}"));
+    bar
+}
+
+fn pause_menu_line(game: &Game, y: usize) -> Option<(Color, String)> {
+    if !game.paused {
+        return None;
+    }
+    let top = HEIGHT / 2 - 3;
+    let title = match game.settings {
+        Some(_) => "RÉGLAGES",
+        None => "PAUSE",
+    };
+    match y.checked_sub(top)? {
+        0 => Some((Color::Yellow, title.to_string())),
+        1 => Some((Color::White, String::new())),
+        row => {
+            let i = row - 2;
+            let (label, locked) = match game.settings {
+                Some(_) => {
+                    let setting = SETTINGS.get(i)?;
+                    (setting.label(game), setting.locked(game))
+                }
+                None => (MENU.get(i)?.label(game), false),
+            };
+            let selected = i == game.settings.unwrap_or(game.menu);
+            let label = if selected {
+                format!("▸ {label} ◂")
+            } else {
+                label
+            };
+            Some(match (locked, selected) {
+                (true, _) => (Color::DarkGrey, label),
+                (false, true) => (Color::Yellow, label),
+                (false, false) => (Color::White, label),
+            })
+        }
+    }
+}
+
+fn key_name(codes: &[KeyCode]) -> String {
+    if codes.is_empty() {
+        return "-".to_string();
+    }
+    codes
+        .iter()
+        .map(|code| match code {
+            KeyCode::Left => "←".to_string(),
+            KeyCode::Right => "→".to_string(),
+            KeyCode::Up => "↑".to_string(),
+            KeyCode::Down => "↓".to_string(),
+            KeyCode::Enter => "Enter".to_string(),
+            KeyCode::Char(' ') => "Space".to_string(),
+            KeyCode::Char(c) => c.to_ascii_uppercase().to_string(),
+            code => format!("{code:?}"),
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn draw(stdout: &mut impl Write, game: &Game, keys: &KeyConfig, origin: u16) -> io::Result<()> {
+    let danger = game.in_danger();
+    let danger_color = match (game.bullet_time(), game.blink(Duration::from_millis(250))) {
+        (true, true) => Color::DarkMagenta,
+        (true, false) => Color::Magenta,
+        (false, true) => Color::DarkRed,
+        (false, false) => Color::Red,
+    };
+    let border_color = if danger { danger_color } else { Color::White };
+
+    let block = game.config.block.as_str();
+    let cell_width = game.config.cell_width();
+    let width = game.width();
+    let board_width = width * cell_width;
+    let blank = " ".repeat(cell_width);
+    let empty = if game.config.show_grid {
+        format!("·{}", " ".repeat(cell_width - 1))
+    } else {
+        blank.clone()
+    };
+    let ghost_y = game.ghost_drop_y();
+    let hold_ghost = game.hold_ghost();
+    let swap_preview = game.swap_preview();
+    let preview_glyph = if cell_width >= 2 {
+        format!("[]{}", " ".repeat(cell_width - 2))
+    } else {
+        "□".to_string()
+    };
+    let lock_left = game
+        .lock_at
+        .filter(|_| game.config.practice || game.config.lock_show_timer)
+        .map(|at| {
+            at.saturating_sub(game.clock).as_secs_f64()
+                / game.config.lock_delay.as_secs_f64().max(f64::EPSILON)
+        });
+    let piece_glyph = match lock_left {
+        Some(left) if left <= 1.0 / 3.0 => "▒".repeat(cell_width),
+        Some(left) if left <= 2.0 / 3.0 => "▓".repeat(cell_width),
+        _ if game.ghost_mode
+            && game.config.ghost_keep_color
+            && (!game.config.flashing || game.blink(GHOST_BLINK)) =>
+        {
+            "▓".repeat(cell_width)
+        }
+        _ => block.to_string(),
+    };
+    let show_pivot = game.config.practice && game.config.show_pivot;
+    let pivot_glyph = format!("{:^cell_width$}", "◆");
+    let title = if board_width >= 20 {
+        "⚡ TETRIS CHAOS ⚡"
+    } else {
+        "TETRIS"
+    };
+
+    queue!(
+        stdout,
+        cursor::MoveTo(origin, 0),
+        SetForegroundColor(border_color),
+        Print("╔"),
+        Print("═".repeat(board_width)),
+        Print("╗"),
+        terminal::Clear(ClearType::UntilNewLine)
+    )?;
+    queue!(
+        stdout,
+        cursor::MoveTo(origin, 1),
+        Print("║"),
+        SetForegroundColor(Color::Red),
+        Print(format!("{:^width$}", title, width = board_width)),
+        SetForegroundColor(border_color),
+        Print("║"),
+        terminal::Clear(ClearType::UntilNewLine)
+    )?;
+    queue!(stdout, cursor::MoveTo(origin, 2), Print("╠"))?;
+    if game.config.column_markers && !game.game_over {
+        for column in 0..width {
+            let x = if game.config.mirror {
+                width - 1 - column
+            } else {
+                column
+            };
+            let occupied = game.current.shape.iter().any(|row| {
+                row.iter()
+                    .enumerate()
+                    .any(|(j, &cell)| cell && game.current_x + j as i32 == x as i32)
+            });
+            if occupied {
+                queue!(
+                    stdout,
+                    SetForegroundColor(game.current.color),
+                    Print(format!("{:═^cell_width$}", "▼")),
+                    SetForegroundColor(border_color)
+                )?;
+            } else {
+                queue!(stdout, Print("═".repeat(cell_width)))?;
+            }
+        }
+    } else {
+        queue!(stdout, Print("═".repeat(board_width)))?;
+    }
+    queue!(stdout, Print("╣"), terminal::Clear(ClearType::UntilNewLine))?;
+
+    let panel = side_panel(game, keys);
+
+    for row in 0..HEIGHT {
+        let y = row + BUFFER_ROWS;
+        let edge_color = if danger && row < DANGER_ROWS {
+            danger_color
+        } else {
+            Color::White
+        };
+        let (left_color, right_color) = match game.tilt {
+            -1 => (Color::Blue, edge_color),
+            1 => (edge_color, Color::Blue),
+            _ => (edge_color, edge_color),
+        };
+        let hammer_row = HEIGHT - row;
+        if game.hammer_mode && game.config.hammer_row_numbers && hammer_row <= 9 {
+            queue!(
+                stdout,
+                cursor::MoveTo(origin, (3 + row) as u16),
+                SetForegroundColor(Color::Yellow),
+                Print(hammer_row)
+            )?;
+        } else if HEIGHT - row <= game.pending_garbage as usize {
+            queue!(
+                stdout,
+                cursor::MoveTo(origin, (3 + row) as u16),
+                SetForegroundColor(Color::Red),
+                Print("┃")
+            )?;
+        } else {
+            queue!(
+                stdout,
+                cursor::MoveTo(origin, (3 + row) as u16),
+                SetForegroundColor(left_color),
+                Print("║")
+            )?;
+        }
+
+        if game.hammer_mode {
+            queue!(stdout, SetForegroundColor(Color::DarkYellow), Print(""))?;
+        }
+
+        let menu_line = pause_menu_line(game, row);
+        if let Some((color, text)) = &menu_line {
+            queue!(
+                stdout,
+                SetAttribute(Attribute::NormalIntensity),
+                SetForegroundColor(*color),
+                Print(format!("{text:^board_width$}"))
+            )?;
+        } else if game.paused {
+            queue!(stdout, SetAttribute(Attribute::Dim))?;
+        }
+
+        for column in 0..width {
+            if menu_line.is_some() {
+                break;
+            }
+            let x = if game.config.mirror {
+                width - 1 - column
+            } else {
+                column
+            };
+
+            let mut drawn = false;
+
+            for (i, row) in game.current.shape.iter().enumerate() {
+                for (j, &cell) in row.iter().enumerate() {
+                    if cell {
+                        let px = game.current_x + j as i32;
+                        let py = game.current_y + i as i32;
+                        if px == x as i32 && py == y as i32 {
+                            let color = if game.ghost_mode && !game.config.ghost_keep_color {
+                                Color::DarkCyan
+                            } else if game.swap_flash() {
+                                Color::White
+                            } else {
+                                game.theme().tint(game.current.color)
+                            };
+                            let glyph = if show_pivot && game.current.is_pivot(i, j) {
+                                &pivot_glyph
+                            } else {
+                                &piece_glyph
+                            };
+                            queue!(stdout, SetForegroundColor(color), Print(glyph))?;
+                            drawn = true;
+                        }
+                    }
+                }
+            }
+
+            if !drawn
+                && let Some((piece, px, py)) = &swap_preview
+                && piece.covers(*px, *py, x, y)
+            {
+                queue!(
+                    stdout,
+                    SetForegroundColor(game.theme().tint(piece.color)),
+                    Print(&preview_glyph)
+                )?;
+                drawn = true;
+            }
+
+            if !drawn
+                && game.board[y][x].is_none()
+                && let Some(swap) = &game.hold_swap
+                && swap.piece.covers(swap.x, swap.y, x, y)
+            {
+                let color = game.theme().tint(swap.piece.color);
+                let color = if game.clock - swap.spawned < SWAP_TTL / 2 {
+                    color
+                } else {
+                    darken(swap.piece.color)
+                };
+                queue!(
+                    stdout,
+                    SetForegroundColor(color),
+                    Print("▒".repeat(cell_width))
+                )?;
+                drawn = true;
+            }
+
+            if !drawn
+                && (game.peeking || !game.config.ghost_on_demand)
+                && game.board[y][x].is_none()
+                && game.current.covers(game.current_x, ghost_y, x, y)
+            {
+                let color = if game.config.ghost_match_piece {
+                    darken(game.current.color)
+                } else {
+                    Color::DarkGrey
+                };
+                queue!(
+                    stdout,
+                    SetForegroundColor(color),
+                    Print("░".repeat(cell_width))
+                )?;
+                drawn = true;
+            }
+
+            if !drawn
+                && game.board[y][x].is_none()
+                && let Some((piece, hold_y)) = &hold_ghost
+                && piece.covers(game.current_x, *hold_y, x, y)
+            {
+                queue!(
+                    stdout,
+                    SetForegroundColor(darken(piece.color)),
+                    Print("╌".repeat(cell_width))
+                )?;
+                drawn = true;
+            }
+
+            if !drawn {
+                match &game.board[y][x] {
+                    Some(CellType::Normal(_, locked))
+                        if game.config.mode == GameMode::Invisible
+                            && !game.game_over
+                            && game.clock - *locked >= game.config.fade =>
+                    {
+                        queue!(stdout, SetForegroundColor(Color::DarkGrey), Print(&empty))?;
+                    }
+                    Some(CellType::Normal(color, locked)) => {
+                        let color = if game.config.rainbow && game.config.flashing {
+                            rainbow(game.clock, x + y)
+                        } else if !game.config.age_dim.is_zero()
+                            && game.clock - *locked >= game.config.age_dim
+                        {
+                            darken(*color)
+                        } else {
+                            game.theme().tint(*color)
+                        };
+                        queue!(stdout, SetForegroundColor(color), Print(block))?;
+                    }
+                    Some(CellType::Garbage) => {
+                        queue!(stdout, SetForegroundColor(Color::DarkGrey), Print(block))?;
+                    }
+                    Some(CellType::Obstacle) => {
+                        queue!(
+                            stdout,
+                            SetForegroundColor(Color::DarkGrey),
+                            Print("▓".repeat(cell_width))
+                        )?;
+                    }
+                    Some(CellType::PowerUp(powerup)) => {
+                        let (symbol, letter) = match powerup {
+                            PowerUpType::Bomb => ("💣", "B"),
+                            PowerUpType::SlowTime => ("⏰", "S"),
+                            PowerUpType::Ghost => ("👻", "G"),
+                            PowerUpType::Hammer => ("🔨", "H"),
+                            PowerUpType::Random => ("🎲", "?"),
+                            PowerUpType::Well => ("📏", "W"),
+                            PowerUpType::Tilt => ("🧲", "M"),
+                        };
+                        let color = powerup.color();
+                        let symbol = if cell_width >= 2 {
+                            format!("{symbol}{}", " ".repeat(cell_width - 2))
+                        } else {
+                            letter.to_string()
+                        };
+                        queue!(stdout, SetForegroundColor(color), Print(symbol))?;
+                    }
+                    None if game.config.flashing
+                        && let Some(burst) = game.bursts.iter().find(|burst| {
+                            burst.x.abs_diff(x) <= 1
+                                && burst.y.abs_diff(y) <= 1
+                                && (burst.x, burst.y) != (x, y)
+                        }) =>
+                    {
+                        queue!(
+                            stdout,
+                            SetForegroundColor(burst.color),
+                            Print(format!("{:^cell_width$}", "✦"))
+                        )?;
+                    }
+                    None => match game
+                        .trails
+                        .iter()
+                        .rev()
+                        .find(|trail| trail.cells.contains(&(x, y)))
+                    {
+                        Some(trail) => {
+                            let color = if game.clock - trail.spawned < TRAIL_TTL / 2 {
+                                trail.color
+                            } else {
+                                darken(trail.color)
+                            };
+                            queue!(
+                                stdout,
+                                SetForegroundColor(color),
+                                Print("│".repeat(cell_width))
+                            )?;
+                        }
+                        None => {
+                            queue!(stdout, SetForegroundColor(Color::DarkGrey), Print(&empty))?;
+                        }
+                    },
+                }
+            }
+        }
+
+        queue!(
+            stdout,
+            SetAttribute(Attribute::NormalIntensity),
+            SetForegroundColor(right_color),
+            Print("║"),
+            SetForegroundColor(Color::White)
+        )?;
+
+        if game.config.layout.side() {
+            panel.draw(stdout, row)?;
+        }
+        queue!(stdout, terminal::Clear(ClearType::UntilNewLine))?;
+    }
+
+    let bottom = (3 + HEIGHT) as u16;
+    queue!(
+        stdout,
+        cursor::MoveTo(origin, bottom),
+        SetForegroundColor(Color::White),
+        Print("╚"),
+        Print("═".repeat(board_width)),
+        Print("╝"),
+        terminal::Clear(ClearType::UntilNewLine)
+    )?;
+
+    if game.config.layout.bottom() {
+        let bar = status_bar(game);
+        for i in 0..STATUS_ROWS {
+            queue!(stdout, cursor::MoveTo(origin, bottom + 1 + i))?;
+            bar.draw(stdout, i as usize)?;
+            queue!(stdout, terminal::Clear(ClearType::UntilNewLine))?;
+        }
+    }
+
+    let results = results_row(&game.config);
+    if game.won {
+        queue!(
+            stdout,
+            cursor::MoveTo(origin, results),
+            SetForegroundColor(Color::Green),
+            Print(format!(
+                "🧀 GAGNÉ 🧀 Temps: {:.2}s",
+                game.clock.as_secs_f64()
+            ))
+        )?;
+    } else if game.game_over && game.config.mode == GameMode::Survival {
+        let secs = game.clock.as_secs_f64();
+        queue!(
+            stdout,
+            cursor::MoveTo(origin, results),
+            SetForegroundColor(Color::Red),
+            Print(format!(
+                "⏱ SURVIE ⏱ Temps: {}:{:05.2}",
+                (secs / 60.0) as u64,
+                secs % 60.0
+            ))
+        )?;
+    } else if game.game_over {
+        queue!(
+            stdout,
+            cursor::MoveTo(origin, results),
+            SetForegroundColor(Color::Red),
+            Print("💀 GAME OVER 💀 Score: "),
+            Print(format!("{}", game.score))
+        )?;
+    }
+
+    if game.game_over {
+        let stats = &game.stats;
+        let average = if stats.pieces > 0 {
+            stats.piece_time.as_secs_f64() / stats.pieces as f64
+        } else {
+            0.0
+        };
+        let mut lines = vec![
+            format!(
+                "Lignes: {}  Pièces: {}",
+                game.lines_cleared_total, stats.pieces
+            ),
+            format!(
+                "Réserve: {}  Rotations ratées: {}",
+                stats.holds, stats.wasted_rotations
+            ),
+            format!("Temps moyen/pièce: {average:.2}s"),
+            format!(
+                "Points: lignes {}  combos {}  nettoyage {}",
+                stats.line_points, stats.combo_points, stats.cleanup_points
+            ),
+            format!(
+                "bombe {}  marteau {}  chute {}  pénalités -{}",
+                stats.bomb_points, stats.hammer_points, stats.drop_points, stats.penalty_points
+            ),
+            match game.config.daily {
+                Some(date) => format!("Défi du jour: {date}"),
+                None => format!("Graine: {}", game.seed),
+            },
+            format!("Empreinte: {:016x}", game.state_hash()),
+        ];
+        let restrictions: Vec<&str> = [
+            (game.config.no_hold, "sans réserve"),
+            (game.config.preview_depth == 0, "sans aperçu"),
+        ]
+        .into_iter()
+        .filter_map(|(active, name)| active.then_some(name))
+        .collect();
+        if game.config.mode == GameMode::PerfectClear || stats.perfect_clears > 0 {
+            lines.insert(
+                1,
+                format!(
+                    "Perfect clears: {}  (+{})",
+                    stats.perfect_clears, stats.perfect_points
+                ),
+            );
+        }
+        if !restrictions.is_empty() {
+            lines.push(format!("Défis: {}", restrictions.join(", ")));
+        }
+        let mode = game.config.mode.label();
+        let mut records = Vec::new();
+        match game.beaten_best {
+            Some(previous) => {
+                records.push(format!("Nouveau record {mode} ! (ancien: {previous})"));
+            }
+            None if game.config.best_score > 0 => {
+                records.push(format!("Record {mode}: {}", game.config.best_score));
+            }
+            None => {}
+        }
+        if let Some(pace) = &game.config.pace {
+            let secs = pace.time.as_secs_f64();
+            records.push(format!(
+                "Meilleur temps: {}:{:05.2}",
+                (secs / 60.0) as u64,
+                secs % 60.0
+            ));
+        }
+        if !game.unlocked_themes.is_empty() {
+            let names: Vec<&str> = game.unlocked_themes.iter().map(|t| t.name()).collect();
+            records.push(format!("Thème débloqué: {}", names.join(", ")));
+        }
+        lines.splice(0..0, records);
+        queue!(stdout, SetForegroundColor(Color::White))?;
+        for (i, line) in lines.iter().enumerate() {
+            queue!(
+                stdout,
+                cursor::MoveTo(origin, results + 1 + i as u16),
+                Print(line)
+            )?;
+        }
+        queue!(
+            stdout,
+            cursor::MoveTo(origin, results + 2 + lines.len() as u16),
+            SetForegroundColor(Color::DarkGrey),
+            Print(if game.config.keep_replay {
+                "R: revoir la partie, autre touche: quitter"
+            } else {
+                "Appuyez sur une touche pour quitter"
+            })
+        )?;
+    }
+
+    for popup in &game.popups {
+        let age = game.clock - popup.spawned;
+        let rise = (age.as_millis() / POPUP_RISE.as_millis()) as usize;
+        let color = if age > POPUP_TTL * 2 / 3 {
+            Color::DarkGrey
+        } else {
+            popup.color
+        };
+        let len = popup.text.chars().count();
+        let x = origin as usize + 1 + board_width.saturating_sub(len) / 2;
+        let y = 3 + popup.row.saturating_sub(BUFFER_ROWS + rise);
+        queue!(
+            stdout,
+            cursor::MoveTo(x as u16, y as u16),
+            SetForegroundColor(color),
+            Print(&popup.text)
+        )?;
+    }
+
+    Ok(())
+}
+
+fn draw_winner(stdout: &mut impl Write, game: &Game, origin: u16) -> io::Result<()> {
+    queue!(
+        stdout,
+        cursor::MoveTo(origin, results_row(&game.config)),
+        SetForegroundColor(Color::Yellow),
+        Print("🏆 VICTOIRE 🏆 Score: "),
+        Print(format!("{}", game.score))
+    )
+}
+
+fn players(config: &Config) -> Vec<KeyConfig> {
+    if config.versus {
+        vec![KeyConfig::versus_right(), KeyConfig::versus_left()]
+    } else {
+        vec![config.keys.clone()]
+    }
+}
+
+fn stride(game: &Game) -> u16 {
+    (game.width() * game.config.cell_width() + 2 + panel_width(&game.config)) as u16
+}
+
+fn new_games(config: &Config, key_releases: bool) -> Vec<Game> {
+    let mut games: Vec<Game> = players(config)
+        .iter()
+        .map(|_| Game::new(config.clone()))
+        .collect();
+    if key_releases {
+        games
+            .iter_mut()
+            .for_each(|game| game.soft_drop_timeout = None);
+    }
+    games
+}
+
+fn step(games: &mut [Game], dt: Duration) {
+    for game in games.iter_mut().filter(|game| !game.paused) {
+        game.update(dt);
+    }
+
+    if games.len() > 1 {
+        let sent: Vec<u32> = games
+            .iter_mut()
+            .map(|game| std::mem::take(&mut game.garbage_out))
+            .collect();
+        let count = games.len();
+        for (i, lines) in sent.into_iter().enumerate() {
+            games[(i + 1) % count].receive_garbage(lines);
+        }
+    }
+}
+
+fn render(out: &mut impl Write, games: &[Game], players: &[KeyConfig]) -> io::Result<()> {
+    let over = games.iter().any(|game| game.game_over);
+    let mut frame = Vec::new();
+    let mut origin = 0;
+    for (game, keys) in games.iter().zip(players) {
+        draw(&mut frame, game, keys, origin)?;
+        if over && games.len() > 1 && !game.game_over {
+            draw_winner(&mut frame, game, origin)?;
+        }
+        origin += stride(game);
+    }
+    if games[0].config.ascii == Some(true) {
+        frame = ascii::convert(&String::from_utf8_lossy(&frame)).into_bytes();
+    }
+    out.write_all(&frame)
+}
+
+fn apply_input(games: &mut [Game], input: Input) {
+    if !matches!(input, Input::Reset | Input::Release(_)) {
+        games[0].reset_pending = false;
+    }
+    match input {
+        Input::Action(player, action) => games[player].apply(action),
+        Input::Release(player) => games[player].release_soft_drop(),
+        Input::Pause(paused) => {
+            for game in games.iter_mut() {
+                game.paused = paused;
+                game.settings = None;
+            }
+        }
+        Input::FallMs(ms) => games.iter_mut().for_each(|game| game.config.fall_ms = ms),
+        Input::HoldRules(first, next_tick) => {
+            for game in games.iter_mut() {
+                game.config.hold_first_piece = first;
+                game.config.hold_next_tick = next_tick;
+            }
+        }
+        Input::Grid => {
+            for game in games.iter_mut() {
+                game.config.show_grid = !game.config.show_grid;
+            }
+        }
+        Input::Reset => {
+            if std::mem::take(&mut games[0].reset_pending) {
+                games[0].soft_reset();
+            } else {
+                games[0].reset_pending = true;
+            }
+        }
+        Input::Hammer(player, digit) => {
+            if games[player].hammer_mode && digit as usize <= HEIGHT {
+                games[player].use_hammer(ROWS - digit as usize);
+            }
+        }
+        Input::CancelHammer(player) => games[player].cancel_hammer(),
+        Input::ArmHammer(player) => games[player].arm_hammer(),
+        Input::Peek(player, on) => games[player].peek(on),
+        Input::Charge(player, dir) => games[player].charge = dir,
+        Input::HoldPreview(on) => games[0].hold_preview = on,
+        Input::Force(typ) => games[0].forced_next = Some(typ),
+        Input::Scenario => games[0].load_scenario(),
+        Input::Restart => {
+            let seed = match games[0].config.daily {
+                Some(_) => games[0].seed,
+                None => games[0].rng.random(),
+            };
+            for game in games.iter_mut() {
+                let mut config = game.config.clone();
+                config.seed = Some(seed);
+                let timeout = game.soft_drop_timeout;
+                *game = Game::new(config);
+                game.soft_drop_timeout = timeout;
+            }
+        }
+    }
+}
+
+fn change_setting(games: &mut [Game], setting: Setting, down: bool) -> Vec<Input> {
+    match setting {
+        Setting::Sound => toggle_mute(),
+        Setting::Volume => set_volume(if down {
+            volume().saturating_sub(1)
+        } else {
+            volume() + 1
+        }),
+        Setting::Ghost => {
+            for game in games.iter_mut() {
+                game.config.ghost_on_demand = !game.config.ghost_on_demand;
+            }
+        }
+        Setting::Grid => return vec![Input::Grid],
+        Setting::Speed => {
+            let fall_ms = games[0].config.fall_ms;
+            let fall_ms = if down {
+                fall_ms.saturating_sub(SPEED_STEP_MS)
+            } else {
+                fall_ms + SPEED_STEP_MS
+            };
+            return vec![Input::FallMs(
+                fall_ms.clamp(SPEED_RANGE_MS.0, SPEED_RANGE_MS.1),
+            )];
+        }
+        Setting::FirstHold | Setting::HoldSpawn => {
+            let config = &games[0].config;
+            let (mut first, mut next_tick) = (config.hold_first_piece, config.hold_next_tick);
+            if setting == Setting::FirstHold {
+                first = !first;
+            } else {
+                next_tick = !next_tick;
+            }
+            return vec![Input::HoldRules(first, next_tick)];
+        }
+        Setting::Theme => {
+            let current = THEMES
+                .iter()
+                .position(|&theme| theme == games[0].config.theme)
+                .unwrap_or(0);
+            let step = if down { THEMES.len() - 1 } else { 1 };
+            let theme = THEMES[(current + step) % THEMES.len()];
+            for game in games.iter_mut() {
+                game.config.theme = theme;
+            }
+        }
+        Setting::Back => {}
+    }
+    Vec::new()
+}
+
+fn close_settings(games: &mut [Game]) {
+    games.iter_mut().for_each(|game| game.settings = None);
+    let Some(path) = games[0].config.path.clone() else {
+        return;
+    };
+    let config = &games[0].config;
+    let values = [
+        ("sound.enabled", (!muted()).to_string()),
+        ("sound.volume", volume().to_string()),
+        ("ghost.on_demand", config.ghost_on_demand.to_string()),
+        ("board.grid", config.show_grid.to_string()),
+        ("speed.fall_ms", config.fall_ms.to_string()),
+        ("hold.first_piece", config.hold_first_piece.to_string()),
+        (
+            "hold.spawn",
+            if config.hold_next_tick {
+                "\"next_tick\"".to_string()
+            } else {
+                "\"immediate\"".to_string()
+            },
+        ),
+        ("theme", format!("\"{}\"", config.theme.key())),
+    ];
+    if config::save(&path, &values).is_err() {
+        games[0].add_popup("ÉCHEC SAUVEGARDE".to_string(), Color::Red, ROWS / 2);
+    }
+}
+
+fn feed(
+    games: &mut [Game],
+    recorder: &mut Option<Recorder>,
+    log: &mut Option<Vec<Entry>>,
+    inputs: Vec<Input>,
+) -> io::Result<()> {
+    for input in inputs {
+        apply_input(games, input);
+        if let Some(recorder) = recorder {
+            recorder.input(input)?;
+        }
+        if let Some(log) = log {
+            log.push(Entry::Input(input));
+        }
+    }
+    Ok(())
+}
+
+fn key_inputs(games: &[Game], players: &[KeyConfig], code: KeyCode) -> Vec<Input> {
+    let paused = games[0].paused;
+    let aiming = games
+        .iter()
+        .enumerate()
+        .filter(|(_, game)| game.hammer_mode)
+        .max_by_key(|(_, game)| game.hammer_armed_at)
+        .map(|(player, _)| player);
+    match code {
+        KeyCode::Char('p') | KeyCode::Char('P') => vec![Input::Pause(!paused)],
+        KeyCode::Char('g') | KeyCode::Char('G') => vec![Input::Grid],
+        _ if paused => Vec::new(),
+        KeyCode::Char('r') | KeyCode::Char('R') if games.len() == 1 => vec![Input::Reset],
+        KeyCode::Char(c @ '1'..='9') if aiming.is_some() => aiming
+            .zip(c.to_digit(10))
+            .map(|(player, digit)| Input::Hammer(player, digit))
+            .into_iter()
+            .collect(),
+        KeyCode::Esc if aiming.is_some() => aiming.map(Input::CancelHammer).into_iter().collect(),
+        KeyCode::Esc if games[0].hold_preview => vec![Input::HoldPreview(false)],
+        KeyCode::Char('v') | KeyCode::Char('V') if games[0].config.practice => {
+            vec![Input::HoldPreview(!games[0].hold_preview)]
+        }
+        code if players
+            .iter()
+            .zip(games)
+            .any(|(keys, game)| keys.arms_hammer(code) && game.hammers > 0) =>
+        {
+            players
+                .iter()
+                .zip(games)
+                .enumerate()
+                .filter(|(_, (keys, game))| keys.arms_hammer(code) && game.hammers > 0)
+                .map(|(player, _)| Input::ArmHammer(player))
+                .collect()
+        }
+        KeyCode::Char('t') | KeyCode::Char('T') if games[0].config.scenario.is_some() => {
+            vec![Input::Scenario]
+        }
+        KeyCode::Char(c @ '1'..='7') if games[0].config.practice => c
+            .to_digit(10)
+            .map(|digit| Input::Force(TetrominoType::ALL[digit as usize - 1]))
+            .into_iter()
+            .collect(),
+        code if games[0].config.ghost_on_demand && players.iter().any(|keys| keys.peeks(code)) => {
+            players
+                .iter()
+                .enumerate()
+                .filter(|(_, keys)| keys.peeks(code))
+                .map(|(player, _)| Input::Peek(player, true))
+                .collect()
+        }
+        code => players
+            .iter()
+            .enumerate()
+            .filter_map(|(player, keys)| Some(Input::Action(player, keys.action(code)?)))
+            .collect(),
+    }
+}
+
+fn export(config: &Config, replay: &Replay, path: &Path) -> io::Result<()> {
+    let players = players(config);
+    let mut games = new_games(config, replay.key_releases);
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(
+        out,
+        "{{\"version\": 2, \"width\": {}, \"height\": {}}}",
+        (stride(&games[0]) as usize + config.cell_width()) * players.len(),
+        results_row(config) + 12
+    )?;
+
+    let mut time = Duration::ZERO;
+    let mut frame = Vec::new();
+    queue!(frame, terminal::Clear(ClearType::All), cursor::Hide)?;
+    let mut last = Vec::new();
+    for entry in &replay.entries {
+        match entry {
+            Entry::Tick(dt) => {
+                step(&mut games, *dt);
+                render(&mut frame, &games, &players)?;
+                if frame != last {
+                    writeln!(out, "{}", replay::cast_line(time, &frame))?;
+                    last = std::mem::take(&mut frame);
+                }
+                frame.clear();
+                time += *dt;
+            }
+            Entry::Input(input) => apply_input(&mut games, *input),
+        }
+    }
+    out.flush()
+}
+
+fn idle_time(frame_start: Instant) -> Duration {
+    FRAME_TIME
+        .saturating_sub(frame_start.elapsed())
+        .max(MIN_IDLE)
+}
+
+fn quit_requested(wait: Duration) -> io::Result<bool> {
+    let until = Instant::now() + wait;
+    while let Some(budget) = until.checked_duration_since(Instant::now()) {
+        if !event::poll(budget)? {
+            break;
+        }
+        if let Event::Key(key) = event::read()?
+            && key.kind == KeyEventKind::Press
+            && quit_key(&key)
+        {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn quit_key(key: &KeyEvent) -> bool {
+    match key.code {
+        KeyCode::Char('q') | KeyCode::Char('Q') => true,
+        KeyCode::Char('c') | KeyCode::Char('C') => key.modifiers.contains(KeyModifiers::CONTROL),
+        _ => false,
+    }
+}
+
+fn main() -> io::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut config = match Config::from_args(args.iter().cloned()) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(2);
+        }
+    };
+
+    if let Some(count) = config.dump_bag {
+        let seed = *config.seed.get_or_insert_with(rand::random);
+        let mut pieces = Randomizer::new(seed, config.bag);
+        let group = if config.bag == 0 { 7 } else { config.bag };
+        let sequence: Vec<String> = (0..count)
+            .collect::<Vec<_>>()
+            .chunks(group)
+            .map(|chunk| chunk.iter().map(|_| pieces.next().name()).collect())
+            .collect();
+        println!("seed {seed}");
+        println!("{}", sequence.join(" "));
+        return Ok(());
+    }
+
+    let replay = match &config.replay {
+        Some(path) => match Replay::load(path) {
+            Ok(replay) => Some(replay),
+            Err(err) => {
+                eprintln!("{err}");
+                std::process::exit(2);
+            }
+        },
+        None => None,
+    };
+    if let (Some(replay), Some(path)) = (&replay, config.replay.take()) {
+        let target = config.export.take();
+        let recorded = match &replay.config {
+            Some(source) => Config::from_replay(&replay.args, source, &replay.board),
+            None => Config::from_args(replay.args.iter().cloned()),
+        };
+        config = match recorded {
+            Ok(config) => config,
+            Err(err) => {
+                eprintln!("{}: {err}", path.display());
+                std::process::exit(2);
+            }
+        };
+        config.seed = Some(replay.seed);
+        config.daily = replay.daily.or(config.daily);
+        if let Some(target) = target {
+            return export(&config, replay, &target);
+        }
+    }
+
+    set_muted(!config.sound);
+    set_volume(config.volume);
+    let scores = config.path.as_deref().map(scores::path);
+    if let Some(path) = &scores {
+        config.best_score = scores::load_best(path, &config.mode.key());
+        config.top_score = scores::load_top(path);
+    }
+    config.pace = scores
+        .as_deref()
+        .zip(config.mode.pace_key())
+        .and_then(|(path, mode)| scores::load_pace(path, &mode));
+    let ascii_notice = config.ascii.is_none() && ascii::limited_terminal();
+    config.ascii.get_or_insert(ascii_notice);
+
+    let mut spectator = match &config.serve {
+        Some((host, port)) => match Spectator::bind(host, *port) {
+            Ok(spectator) => Some(spectator),
+            Err(err) => {
+                eprintln!("--serve {host}:{port}: {err}");
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    let mut stdout = io::stdout();
+    terminal::enable_raw_mode()?;
+    execute!(
+        stdout,
+        terminal::Clear(ClearType::All),
+        cursor::Hide,
+        EnableFocusChange
+    )?;
+    let key_releases = terminal::supports_keyboard_enhancement().unwrap_or(false);
+    if key_releases {
+        execute!(
+            stdout,
+            PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+        )?;
+    }
+
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let mut stdout = io::stdout();
+        let _ = execute!(
+            stdout,
+            terminal::Clear(ClearType::All),
+            cursor::MoveTo(0, 0)
+        );
+        let _ = restore_terminal(&mut stdout, key_releases);
+        default_hook(info);
+    }));
+
+    let mut players = players(&config);
+    let seed = *config.seed.get_or_insert_with(rand::random);
+
+    if let Some(replay) = &replay {
+        watch(&mut stdout, &config, replay, &players, &mut spectator)?;
+        return restore_terminal(&mut stdout, key_releases);
+    }
+
+    if config.demo {
+        let mut games = new_games(&config, key_releases);
+        let mut demos: Vec<Demo> = games.iter().map(|_| Demo::default()).collect();
+        let mut last_update = Instant::now();
+        loop {
+            let frame_start = Instant::now();
+            step(&mut games, (frame_start - last_update).min(MAX_FRAME_STEP));
+            last_update = frame_start;
+            for (player, demo) in demos.iter_mut().enumerate() {
+                if let Some(action) = demo.next_action(&games[player]) {
+                    apply_input(&mut games, Input::Action(player, action));
+                }
+            }
+
+            render(&mut stdout, &games, &players)?;
+            stdout.flush()?;
+            if let Some(spectator) = &mut spectator {
+                spectator.send(&games[0]);
+            }
+
+            if games.iter().any(|game| game.game_over) {
+                if quit_requested(DEMO_RESTART)? {
+                    break;
+                }
+                config.seed = Some(rand::random());
+                games = new_games(&config, key_releases);
+                demos = games.iter().map(|_| Demo::default()).collect();
+                last_update = Instant::now();
+                queue!(stdout, terminal::Clear(ClearType::All))?;
+            } else if quit_requested(idle_time(frame_start))? {
+                break;
+            }
+        }
+        return restore_terminal(&mut stdout, key_releases);
+    }
+
+    let mut recorder = match &config.record {
+        Some(path) => Some(Recorder::create(path, &args, &config, seed, key_releases)?),
+        None => None,
+    };
+    let mut games = new_games(&config, key_releases);
+    if ascii_notice {
+        games[0].add_popup("MODE ASCII".to_string(), Color::Yellow, ROWS / 2);
+    }
+    let mut last_update = Instant::now();
+    let mut focus_paused = false;
+    let mut repeats = KeyRepeat::new(key_releases);
+    let mut summary = None;
+    let mut alt_keys = config.alt_keys.clone();
+    let mut log = config.keep_replay.then(Vec::new);
+    let mut rewatch = None;
+
+    'game: loop {
+        let frame_start = Instant::now();
+        let dt = (frame_start - last_update).min(MAX_FRAME_STEP);
+        step(&mut games, dt);
+        last_update = frame_start;
+        if let Some(recorder) = &mut recorder {
+            recorder.tick(dt)?;
+        }
+        if let Some(log) = &mut log {
+            log.push(Entry::Tick(dt));
+        }
+
+        if let ([game], Some(path)) = (games.as_mut_slice(), &scores)
+            && game.game_over
+            && !game.config.practice
+        {
+            let mut saved = Ok(());
+            if game.score > game.config.best_score {
+                game.beaten_best = Some(game.config.best_score);
+                game.config.best_score = game.score;
+                saved = saved.and(scores::save_best(path, &game.config.mode.key(), game.score));
+            }
+            if game.score > game.config.top_score {
+                let top = game.config.top_score + 1..=game.score;
+                game.unlocked_themes = THEMES
+                    .into_iter()
+                    .filter(|theme| top.contains(&theme.unlock_score()))
+                    .collect();
+                game.config.top_score = game.score;
+            }
+            if let Some(pace) = game.pace_record()
+                && let Some(mode) = game.config.mode.pace_key()
+            {
+                saved = saved.and(scores::save_pace(path, &mode, &pace));
+                game.config.pace = Some(pace);
+            }
+            if saved.is_err() {
+                game.add_popup("ÉCHEC SAUVEGARDE".to_string(), Color::Red, ROWS / 2);
+            }
+        }
+
+        render(&mut stdout, &games, &players)?;
+        stdout.flush()?;
+        if let Some(spectator) = &mut spectator {
+            spectator.send(&games[0]);
+        }
+
+        if games.iter().any(|game| game.game_over) {
+            if let [game] = games.as_slice() {
+                summary = Some(share::summary(game));
+            }
+            let key = loop {
+                if let Event::Key(key) = event::read()?
+                    && key.kind == KeyEventKind::Press
+                {
+                    break key;
+                }
+            };
+            if matches!(key.code, KeyCode::Char('r') | KeyCode::Char('R'))
+                && let Some(entries) = log.take()
+            {
+                rewatch = Some(Replay {
+                    args: Vec::new(),
+                    config: None,
+                    board: Vec::new(),
+                    seed,
+                    daily: config.daily,
+                    key_releases,
+                    entries,
+                });
+            }
+            break;
+        }
+
+        feed(&mut games, &mut recorder, &mut log, repeats.expire())?;
+        let deadline = Instant::now() + idle_time(frame_start);
+        while let Some(budget) = deadline.checked_duration_since(Instant::now()) {
+            if !event::poll(budget)? {
+                break;
+            }
+            let paused = games[0].paused;
+            let inputs = match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Release => players
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(player, keys)| {
+                        if keys.action(key.code) == Some(Action::SoftDrop) {
+                            Some(Input::Release(player))
+                        } else if keys.peeks(key.code) && games[player].peeking {
+                            Some(Input::Peek(player, false))
+                        } else {
+                            None
+                        }
+                    })
+                    .collect(),
+                Event::Key(key) if quit_key(&key) => {
+                    break 'game;
+                }
+                Event::Key(key)
+                    if key.code == KeyCode::Tab
+                        && key.kind == KeyEventKind::Press
+                        && !config.versus =>
+                {
+                    std::mem::swap(&mut players[0], &mut alt_keys);
+                    vec![Input::Release(0)]
+                }
+                Event::Key(key) if key.code == KeyCode::F(2) && key.kind == KeyEventKind::Press => {
+                    let mut frame = Vec::new();
+                    render(&mut frame, &games, &players)?;
+                    let (text, color) = match screenshot::save(&frame) {
+                        Ok(_) => ("CAPTURÉ", Color::Green),
+                        Err(_) => ("ÉCHEC CAPTURE", Color::Red),
+                    };
+                    games[0].add_popup(text.to_string(), color, ROWS / 2);
+                    continue;
+                }
+                Event::Key(key) if paused && games[0].settings.is_some() => {
+                    let selected = games[0].settings.unwrap_or(0);
+                    let count = SETTINGS.len();
+                    match (key.code, SETTINGS[selected]) {
+                        (KeyCode::Up | KeyCode::Down, _) => {
+                            let step = if key.code == KeyCode::Up {
+                                count - 1
+                            } else {
+                                1
+                            };
+                            let selected = Some((selected + step) % count);
+                            games.iter_mut().for_each(|game| game.settings = selected);
+                            Vec::new()
+                        }
+                        (KeyCode::Esc, _)
+                        | (KeyCode::Enter | KeyCode::Char(' '), Setting::Back) => {
+                            close_settings(&mut games);
+                            Vec::new()
+                        }
+                        (
+                            KeyCode::Enter | KeyCode::Char(' ') | KeyCode::Left | KeyCode::Right,
+                            setting,
+                        ) => change_setting(&mut games, setting, key.code == KeyCode::Left),
+                        (code, _) => {
+                            if matches!(code, KeyCode::Char('p') | KeyCode::Char('P')) {
+                                focus_paused = false;
+                                close_settings(&mut games);
+                            }
+                            key_inputs(&games, &players, code)
+                        }
+                    }
+                }
+                Event::Key(key) if paused => {
+                    let count = MENU.len();
+                    match (key.code, MENU[games[0].menu]) {
+                        (KeyCode::Up | KeyCode::Down, _) => {
+                            let step = if key.code == KeyCode::Up {
+                                count - 1
+                            } else {
+                                1
+                            };
+                            let menu = (games[0].menu + step) % count;
+                            games.iter_mut().for_each(|game| game.menu = menu);
+                            Vec::new()
+                        }
+                        (
+                            KeyCode::Enter | KeyCode::Char(' ') | KeyCode::Left | KeyCode::Right,
+                            item,
+                        ) => match item {
+                            MenuItem::Resume => {
+                                focus_paused = false;
+                                vec![Input::Pause(false)]
+                            }
+                            MenuItem::Restart => {
+                                focus_paused = false;
+                                vec![Input::Restart]
+                            }
+                            MenuItem::Sound => {
+                                toggle_mute();
+                                Vec::new()
+                            }
+                            MenuItem::Grid => vec![Input::Grid],
+                            MenuItem::Settings => {
+                                games.iter_mut().for_each(|game| game.settings = Some(0));
+                                Vec::new()
+                            }
+                            MenuItem::Quit => break 'game,
+                        },
+                        (code, _) => {
+                            if matches!(code, KeyCode::Char('p') | KeyCode::Char('P')) {
+                                focus_paused = false;
+                            }
+                            key_inputs(&games, &players, code)
+                        }
+                    }
+                }
+                Event::Key(key) => {
+                    let inputs = key_inputs(&games, &players, key.code);
+                    repeats.filter(&config, &key, inputs)
+                }
+                Event::FocusLost if !paused => {
+                    focus_paused = true;
+                    vec![Input::Pause(true)]
+                }
+                Event::FocusGained if focus_paused => {
+                    focus_paused = false;
+                    vec![Input::Pause(false)]
+                }
+                _ => continue,
+            };
+            feed(&mut games, &mut recorder, &mut log, inputs)?;
+        }
+    }
+
+    if let Some(recorder) = recorder {
+        recorder.finish()?;
+    }
+    if let Some(replay) = &rewatch {
+        watch(&mut stdout, &config, replay, &players, &mut spectator)?;
+    }
+    restore_terminal(&mut stdout, key_releases)?;
+    if let Some(summary) = summary {
+        execute!(
+            stdout,
+            terminal::Clear(ClearType::All),
+            cursor::MoveTo(0, 0)
+        )?;
+        if config.ascii == Some(true) {
+            print!("{}", ascii::convert(&summary));
+        } else {
+            print!("{summary}");
+        }
+        share::copy(&mut stdout, &summary)?;
+    }
+    if ascii_notice {
+        let (_, rows) = terminal::size()?;
+        execute!(stdout, cursor::MoveTo(0, rows.saturating_sub(1)))?;
+        eprintln!(
+            "tetris-tui: this terminal does not look UTF-8 capable, so ASCII mode was used (--utf8 to override)"
+        );
+    }
+    Ok(())
+}
+
+fn watch(
+    stdout: &mut io::Stdout,
+    config: &Config,
+    replay: &Replay,
+    players: &[KeyConfig],
+    spectator: &mut Option<Spectator>,
+) -> io::Result<()> {
+    'watch: loop {
+        let mut games = new_games(config, replay.key_releases);
+        queue!(stdout, terminal::Clear(ClearType::All))?;
+        for entry in &replay.entries {
+            match entry {
+                Entry::Tick(dt) => {
+                    step(&mut games, *dt);
+                    render(stdout, &games, players)?;
+                    stdout.flush()?;
+                    if let Some(spectator) = spectator {
+                        spectator.send(&games[0]);
+                    }
+                    if quit_requested((*dt).max(MIN_IDLE))? {
+                        return Ok(());
+                    }
+                }
+                Entry::Input(input) => apply_input(&mut games, *input),
+            }
+        }
+        loop {
+            if let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+            {
+                if config.keep_replay && matches!(key.code, KeyCode::Char('r') | KeyCode::Char('R'))
+                {
+                    continue 'watch;
+                }
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn restore_terminal(stdout: &mut io::Stdout, key_releases: bool) -> io::Result<()> {
+    terminal::disable_raw_mode()?;
+    if key_releases {
+        execute!(stdout, PopKeyboardEnhancementFlags)?;
+    }
+    execute!(stdout, DisableFocusChange, cursor::Show)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, slice};
+
+    fn game(rows: &[&str]) -> Game {
+        set_muted(true);
+        Game::new(Config {
+            seed: Some(1),
+            power_up_chance: 0.0,
+            obstacle_chance: 0.0,
+            start_board: Some(config::parse_board("test", &rows.join("\n")).unwrap()),
+            ..Config::default()
+        })
+    }
+
+    fn place(game: &mut Game, typ: TetrominoType, x: i32, y: i32) {
+        game.current = Tetromino::new(typ);
+        game.current_x = x;
+        game.current_y = y;
+    }
+
+    fn play(game: &mut Game, actions: &[Action]) {
+        let games = slice::from_mut(game);
+        for &action in actions {
+            apply_input(games, Input::Action(0, action));
+            step(games, FRAME_TIME);
+        }
+    }
+
+    fn tick(game: &mut Game, dt: Duration) {
+        step(slice::from_mut(game), dt);
+    }
+
+    fn dump(game: &Game) -> Vec<String> {
+        game.board
+            .iter()
+            .skip(BUFFER_ROWS)
+            .map(|row| spectator::board_row(row))
+            .collect()
+    }
+
+    fn cells(piece: &Tetromino) -> Vec<(usize, usize)> {
+        (0..piece.shape.len())
+            .flat_map(|i| (0..piece.shape.len()).map(move |j| (i, j)))
+            .filter(|&(i, j)| piece.shape[i][j])
+            .collect()
+    }
+
+    fn board(rows: &[&str]) -> Vec<String> {
+        let mut board = vec![".".repeat(WIDTH); HEIGHT - rows.len()];
+        board.extend(rows.iter().map(|row| row.to_string()));
+        board
+    }
+
+    #[test]
+    fn hard_drop_clears_the_line_it_completes() {
+        let mut game = game(&["#.........", "######...."]);
+        place(&mut game, TetrominoType::I, 3, 1);
+        play(
+            &mut game,
+            &[
+                Action::Right,
+                Action::Right,
+                Action::Right,
+                Action::HardDrop,
+            ],
+        );
+        assert_eq!(dump(&game), board(&["#........."]));
+        assert_eq!(game.lines_cleared_total, 1);
+    }
+
+    #[test]
+    fn vertical_i_clears_every_row_it_completes() {
+        let mut game = game(&["..#.......", "#########.", "#.#.......", "#########."]);
+        place(&mut game, TetrominoType::I, 3, 1);
+        play(
+            &mut game,
+            &[
+                Action::Rotate,
+                Action::Right,
+                Action::Right,
+                Action::Right,
+                Action::Right,
+                Action::HardDrop,
+            ],
+        );
+        assert_eq!(dump(&game), board(&["..#......#", "#.#......#"]));
+        assert_eq!(game.lines_cleared_total, 2);
+    }
+
+    #[test]
+    fn obstacles_and_gaps_keep_rows_from_clearing() {
+        let mut game = game(&["X########.", "########.."]);
+        place(&mut game, TetrominoType::I, 3, 1);
+        play(
+            &mut game,
+            &[
+                Action::Rotate,
+                Action::Right,
+                Action::Right,
+                Action::Right,
+                Action::Right,
+                Action::HardDrop,
+            ],
+        );
+        assert_eq!(
+            dump(&game),
+            board(&[".........#", ".........#", "X#########", "########.#"])
+        );
+        assert_eq!(game.lines_cleared_total, 0);
+    }
+
+    #[test]
+    fn gravity_ticks_lock_the_piece_on_the_stack() {
+        let mut game = game(&["#########."]);
+        place(&mut game, TetrominoType::O, 4, 1);
+        while game.stats.pieces == 0 {
+            tick(&mut game, Duration::from_millis(100));
+        }
+        assert_eq!(
+            dump(&game),
+            board(&["....##....", "....##....", "#########."])
+        );
+        assert_eq!(game.lines_cleared_total, 0);
+    }
+
+    #[test]
+    fn gravity_ticks_clear_completed_lines() {
+        let mut game = game(&["####..####", "####..####"]);
+        place(&mut game, TetrominoType::O, 4, 1);
+        while game.stats.pieces == 0 {
+            tick(&mut game, Duration::from_millis(100));
+        }
+        assert_eq!(dump(&game), board(&[]));
+        assert_eq!(game.lines_cleared_total, 2);
+    }
+
+    #[test]
+    fn srs_kicks_off_the_wall() {
+        let mut game = game(&[".........."]);
+        game.config.rotation = RotationSystem::Srs;
+        place(&mut game, TetrominoType::T, -1, 10);
+        game.current.rotate();
+        play(&mut game, &[Action::Rotate]);
+        assert_eq!(game.current.rotation, 2);
+        assert_eq!((game.current_x, game.current_y), (0, 10));
+    }
+
+    #[test]
+    fn simple_rotation_does_not_kick() {
+        let mut game = game(&[".........."]);
+        place(&mut game, TetrominoType::T, -1, 10);
+        game.current.rotate();
+        play(&mut game, &[Action::Rotate]);
+        assert_eq!(game.current.rotation, 1);
+        assert_eq!((game.current_x, game.current_y), (-1, 10));
+        assert_eq!(game.stats.wasted_rotations, 1);
+    }
+
+    #[test]
+    fn srs_kicks_the_i_piece_up_from_the_floor() {
+        let mut game = game(&[".........."]);
+        game.config.rotation = RotationSystem::Srs;
+        let floor = ROWS as i32 - 2;
+        place(&mut game, TetrominoType::I, 3, floor);
+        play(&mut game, &[Action::Rotate]);
+        assert_eq!(game.current.rotation, 1);
+        assert_eq!((game.current_x, game.current_y), (4, floor - 2));
+    }
+
+    #[test]
+    fn locking_over_a_bomb_scores_the_board_before_the_piece() {
+        let mut game = game(&["#...b....#", "##.#.b#.##"]);
+        place(&mut game, TetrominoType::O, 4, ROWS as i32 - 2);
+        game.lock_piece();
+        assert_eq!(dump(&game), board(&["#...##...#", "##..##..##"]));
+        assert_eq!(game.score, 20);
+    }
+
+    #[test]
+    fn ghost_lock_collects_a_power_up_once() {
+        let mut game = game(&["....h.....", "....##...."]);
+        game.ghost_mode = true;
+        game.ghost_remaining = 2;
+        place(&mut game, TetrominoType::O, 4, ROWS as i32 - 2);
+        game.lock_piece();
+        assert_eq!(dump(&game), board(&["....##....", "....##...."]));
+        assert!(game.hammer_mode);
+        assert_eq!(game.score, 0);
+    }
+
+    #[test]
+    fn rotation_states_match_the_guideline() {
+        type States = [[(usize, usize); 4]; 4];
+        let states: [(TetrominoType, States); 7] = [
+            (
+                TetrominoType::I,
+                [
+                    [(1, 0), (1, 1), (1, 2), (1, 3)],
+                    [(0, 2), (1, 2), (2, 2), (3, 2)],
+                    [(2, 0), (2, 1), (2, 2), (2, 3)],
+                    [(0, 1), (1, 1), (2, 1), (3, 1)],
+                ],
+            ),
+            (TetrominoType::O, [[(0, 0), (0, 1), (1, 0), (1, 1)]; 4]),
+            (
+                TetrominoType::T,
+                [
+                    [(0, 1), (1, 0), (1, 1), (1, 2)],
+                    [(0, 1), (1, 1), (1, 2), (2, 1)],
+                    [(1, 0), (1, 1), (1, 2), (2, 1)],
+                    [(0, 1), (1, 0), (1, 1), (2, 1)],
+                ],
+            ),
+            (
+                TetrominoType::S,
+                [
+                    [(0, 1), (0, 2), (1, 0), (1, 1)],
+                    [(0, 1), (1, 1), (1, 2), (2, 2)],
+                    [(1, 1), (1, 2), (2, 0), (2, 1)],
+                    [(0, 0), (1, 0), (1, 1), (2, 1)],
+                ],
+            ),
+            (
+                TetrominoType::Z,
+                [
+                    [(0, 0), (0, 1), (1, 1), (1, 2)],
+                    [(0, 2), (1, 1), (1, 2), (2, 1)],
+                    [(1, 0), (1, 1), (2, 1), (2, 2)],
+                    [(0, 1), (1, 0), (1, 1), (2, 0)],
+                ],
+            ),
+            (
+                TetrominoType::J,
+                [
+                    [(0, 0), (1, 0), (1, 1), (1, 2)],
+                    [(0, 1), (0, 2), (1, 1), (2, 1)],
+                    [(1, 0), (1, 1), (1, 2), (2, 2)],
+                    [(0, 1), (1, 1), (2, 0), (2, 1)],
+                ],
+            ),
+            (
+                TetrominoType::L,
+                [
+                    [(0, 2), (1, 0), (1, 1), (1, 2)],
+                    [(0, 1), (1, 1), (2, 1), (2, 2)],
+                    [(1, 0), (1, 1), (1, 2), (2, 0)],
+                    [(0, 0), (0, 1), (1, 1), (2, 1)],
+                ],
+            ),
+        ];
+        for (typ, expected) in states {
+            let mut piece = Tetromino::new(typ);
+            for (rotation, cells_at) in expected.iter().enumerate() {
+                assert_eq!(piece.rotation, rotation);
+                assert_eq!(
+                    cells(&piece),
+                    cells_at,
+                    "{} rotation {rotation}",
+                    typ.name()
+                );
+                piece.rotate();
+            }
+            assert_eq!(piece.rotation, 0);
+            assert_eq!(cells(&piece), expected[0]);
+        }
+    }
+
+    fn settle(board: &mut [Vec<Option<CellType>>]) {
+        for _ in 0..ROWS {
+            for y in (0..ROWS - 1).rev() {
+                for x in 0..board[y].len() {
+                    if board[y][x].is_some() && board[y + 1][x].is_none() {
+                        board[y + 1][x] = board[y][x].take();
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn apply_gravity_drops_floating_cells() {
+        let mut game = game(&[
+            "#..b......",
+            "..........",
+            ".#..X...#.",
+            "#.........",
+            "..##.....#",
+        ]);
+        game.apply_gravity();
+        assert_eq!(dump(&game), board(&["#..b......", "####X...##"]));
+    }
+
+    #[test]
+    fn apply_gravity_matches_a_row_by_row_settle() {
+        let mut rng = ChaCha12Rng::seed_from_u64(7);
+        let cells = ['.', '.', '.', '#', 'X', 'b', 'T'];
+        for _ in 0..50 {
+            let rows: Vec<String> = (0..HEIGHT)
+                .map(|_| (0..WIDTH).map(|_| cells[rng.random_range(0..7)]).collect())
+                .collect();
+            let rows: Vec<&str> = rows.iter().map(String::as_str).collect();
+            let mut game = game(&rows);
+            let mut expected = game.board.clone();
+            settle(&mut expected);
+            game.apply_gravity();
+            assert!(game.board == expected, "{rows:?}");
+        }
+    }
+
+    fn stack(top: &str) -> Vec<&str> {
+        let mut rows = vec!["#########."; HEIGHT];
+        rows[0] = top;
+        rows
+    }
+
+    #[test]
+    fn spawn_waits_in_the_buffer_above_a_high_stack() {
+        let mut game = game(&stack("...#.....#"));
+        game.current = Tetromino::new(TetrominoType::T);
+        game.enter_field();
+        assert!(!game.game_over);
+        assert_eq!(game.current_y, 0);
+    }
+
+    #[test]
+    fn spawn_drops_into_view_when_there_is_room() {
+        let mut game = game(&stack(".........#"));
+        game.current = Tetromino::new(TetrominoType::T);
+        game.enter_field();
+        assert!(!game.game_over);
+        assert_eq!(game.current_y, 1);
+    }
+
+    #[test]
+    fn blocked_spawn_tops_out() {
+        let mut game = game(&stack(".........#"));
+        game.board[1][4] = Some(CellType::Garbage);
+        game.current = Tetromino::new(TetrominoType::T);
+        game.enter_field();
+        assert!(game.game_over);
+    }
+
+    #[test]
+    fn locking_inside_the_buffer_tops_out() {
+        let mut game = game(&stack("...#.....#"));
+        game.current = Tetromino::new(TetrominoType::T);
+        game.enter_field();
+        play(&mut game, &[Action::HardDrop]);
+        assert!(game.game_over);
+    }
+
+    #[test]
+    fn locking_partly_in_view_keeps_playing() {
+        let mut game = game(&stack("..#######."));
+        place(&mut game, TetrominoType::S, 0, 1);
+        game.forced_next = Some(TetrominoType::T);
+        play(&mut game, &[Action::HardDrop]);
+        assert!(!game.game_over);
+        assert!(game.board[1][1..3].iter().all(Option::is_some));
+        assert_eq!(dump(&game)[0], "#########.");
+        assert_eq!(game.current_y, 0);
+    }
+
+    #[test]
+    fn state_hash_is_stable_across_clones() {
+        let mut game = game(&["#..b.X...#", "####.#####"]);
+        tick(&mut game, Duration::from_millis(600));
+        let mut copy = game.clone();
+        assert_eq!(copy.state_hash(), game.state_hash());
+        for _ in 0..20 {
+            play(&mut game, &[Action::HardDrop]);
+            play(&mut copy, &[Action::HardDrop]);
+            tick(&mut game, Duration::from_millis(100));
+            tick(&mut copy, Duration::from_millis(100));
+        }
+        assert_eq!(copy.state_hash(), game.state_hash());
+    }
+
+    #[test]
+    fn state_hash_changes_with_the_board() {
+        let game = game(&["#..b.X...#", "####.#####"]);
+        let hash = game.state_hash();
+        let cells = [
+            None,
+            Some(CellType::Normal(Color::Red, Duration::ZERO)),
+            Some(CellType::Garbage),
+            Some(CellType::Obstacle),
+            Some(CellType::PowerUp(PowerUpType::Bomb)),
+            Some(CellType::PowerUp(PowerUpType::SlowTime)),
+            Some(CellType::PowerUp(PowerUpType::Ghost)),
+            Some(CellType::PowerUp(PowerUpType::Hammer)),
+            Some(CellType::PowerUp(PowerUpType::Random)),
+            Some(CellType::PowerUp(PowerUpType::Well)),
+            Some(CellType::PowerUp(PowerUpType::Tilt)),
+        ];
+        let mut hashes: Vec<u64> = cells
+            .into_iter()
+            .map(|cell| {
+                let mut copy = game.clone();
+                copy.board[ROWS - 1][4] = cell;
+                copy.state_hash()
+            })
+            .collect();
+        assert_eq!(hashes[0], hash);
+        hashes.sort();
+        hashes.dedup();
+        assert_eq!(hashes.len(), cells.len());
+        let mut copy = game.clone();
+        copy.board[ROWS - 2][3] = None;
+        assert_ne!(copy.state_hash(), hash);
+    }
+
+    #[test]
+    fn each_bag_deals_every_piece_once() {
+        for seed in 0..20 {
+            let mut pieces = Randomizer::new(seed, 7);
+            for _ in 0..10 {
+                let mut counts = [0; 7];
+                for _ in 0..7 {
+                    counts[pieces.next() as usize] += 1;
+                }
+                assert_eq!(counts, [1; 7], "seed {seed}");
+            }
+        }
+    }
+
+    #[test]
+    fn larger_bags_deal_every_piece_evenly() {
+        let mut pieces = Randomizer::new(3, 14);
+        for _ in 0..10 {
+            let mut counts = [0; 7];
+            for _ in 0..14 {
+                counts[pieces.next() as usize] += 1;
+            }
+            assert_eq!(counts, [2; 7]);
+        }
+    }
+
+    #[test]
+    fn buffer_rows_hold_pieces() {
+        let game = game(&[".........."]);
+        let piece = Tetromino::new(TetrominoType::O);
+        assert!(game.fits(&piece, 4, 0));
+        assert!(game.fits(&piece, 0, BUFFER_ROWS as i32 - 1));
+        assert!(!game.fits(&piece, 4, -1));
+    }
+
+    #[test]
+    fn buffer_cells_collide_like_the_field() {
+        let mut game = game(&["....#....."]);
+        let piece = Tetromino::new(TetrominoType::O);
+        for cell in [
+            CellType::Garbage,
+            CellType::Obstacle,
+            CellType::Normal(Color::Red, Duration::ZERO),
+        ] {
+            game.board[0][5] = Some(cell);
+            assert!(!game.fits(&piece, 4, 0));
+            assert!(!game.fits(&piece, 5, 0));
+            assert!(game.fits(&piece, 6, 0));
+        }
+        game.board[0][5] = Some(CellType::PowerUp(PowerUpType::Bomb));
+        assert!(game.fits(&piece, 4, 0));
+    }
+
+    #[test]
+    fn pieces_straddling_the_buffer_hit_the_top_row() {
+        let mut game = game(&stack("....#....#"));
+        let piece = Tetromino::new(TetrominoType::O);
+        assert!(game.fits(&piece, 0, BUFFER_ROWS as i32 - 1));
+        assert!(!game.fits(&piece, 3, BUFFER_ROWS as i32 - 1));
+        assert!(game.fits(&piece, 3, BUFFER_ROWS as i32 - 2));
+        game.ghost_mode = true;
+        assert!(game.fits(&piece, 3, BUFFER_ROWS as i32 - 1));
+    }
+
+    #[test]
+    fn well_does_not_shrink_over_occupied_cells() {
+        let mut game = game(&["........#.", ".........h"]);
+        assert!(!game.resize_well(WIDTH - 1));
+        assert_eq!(game.width(), WIDTH);
+        assert_eq!(dump(&game), board(&["........#.", ".........h"]));
+        game.board[ROWS - 1][WIDTH - 1] = None;
+        assert!(game.resize_well(WIDTH - 1));
+        assert_eq!(dump(&game)[HEIGHT - 2..], ["........#", "........."]);
+    }
+
+    #[test]
+    fn wide_wells_take_their_own_room() {
+        let mut game = game(&[".........."]);
+        let narrow = stride(&game);
+        assert!(game.resize_well(WIDTH + 1));
+        assert_eq!(stride(&game), narrow + game.config.cell_width() as u16);
+        assert!(game.resize_well(WIDTH));
+        assert_eq!(stride(&game), narrow);
+    }
+
+    #[test]
+    fn side_panel_fits_beside_the_board() {
+        let mut game = game(&[".........."]);
+        game.ghost_mode = true;
+        game.slow_time_active = true;
+        game.hammers = 1;
+        game.tilt = 1;
+        game.well_until = Some(WELL_TIME);
+        game.reset_pending = true;
+        for config in [
+            Config::default(),
+            Config {
+                practice: true,
+                ..Config::default()
+            },
+            Config {
+                versus: true,
+                ..Config::default()
+            },
+            Config {
+                chaos: true,
+                ..Config::default()
+            },
+            Config {
+                goal: Some(40),
+                ..Config::default()
+            },
+            Config {
+                chaos: true,
+                goal: Some(40),
+                scenario: Some(1),
+                ..Config::default()
+            },
+        ] {
+            game.config = config;
+            assert!(side_panel(&game, &game.config.keys).height() <= HEIGHT);
+        }
+    }
+
+    #[test]
+    fn tilt_settles_the_stack_against_the_wall() {
+        let mut game = game(&["....#.....", "..........", "#.#..X...b", ".##...#..#"]);
+        game.tilt = -1;
+        game.apply_gravity();
+        assert_eq!(
+            dump(&game),
+            board(&["#.........", "##Xb......", "####......"])
+        );
+        game.tilt = 1;
+        game.apply_gravity();
+        assert_eq!(
+            dump(&game),
+            board(&[".........#", "......##Xb", "......####"])
+        );
+    }
+
+    #[test]
+    fn tilt_leaves_the_falling_piece_in_its_column() {
+        let mut game = game(&[".........."]);
+        game.tilt = 1;
+        game.tilt_remaining = TILT_PIECES;
+        let x = game.current_x;
+        game.update(Duration::from_secs(2));
+        assert_eq!(game.current_x, x);
+    }
+
+    #[test]
+    fn tilted_locks_pull_the_piece_to_the_wall() {
+        let mut game = game(&["##........"]);
+        game.tilt = -1;
+        game.tilt_remaining = TILT_PIECES;
+        place(&mut game, TetrominoType::O, 6, 1);
+        game.apply(Action::HardDrop);
+        assert_eq!(dump(&game), board(&["##........", "####......"]));
+        assert_eq!(game.tilt_remaining, TILT_PIECES - 1);
+    }
+
+    #[test]
+    fn goal_keeps_its_row_through_combos() {
+        let mut game = game(&[".........."]);
+        for chaos in [false, true] {
+            game.config = Config {
+                chaos,
+                goal: Some(40),
+                ..Config::default()
+            };
+            for combo in [0, 3] {
+                game.combo = combo;
+                let panel = side_panel(&game, &game.config.keys);
+                assert_eq!(panel.row(1), game.goal_line().unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn lock_timeout_starts_when_gravity_lands_the_piece() {
+        let mut game = game(&[".........."]);
+        game.config.lock_timeout = Duration::from_millis(100);
+        place(&mut game, TetrominoType::O, 4, ROWS as i32 - 3);
+        game.lowest_y = game.current_y;
+        game.update(game.get_fall_speed());
+        assert_eq!(game.grounded_at, Some(game.clock));
+        assert_eq!(game.stats.pieces, 0);
+        game.update(Duration::from_millis(150));
+        assert_eq!(game.stats.pieces, 1);
+    }
+
+    #[test]
+    fn lock_timeout_starts_when_soft_drop_lands_the_piece() {
+        let mut game = game(&[".........."]);
+        game.config.lock_timeout = Duration::from_millis(100);
+        place(&mut game, TetrominoType::O, 4, ROWS as i32 - 3);
+        game.apply(Action::SoftDrop);
+        game.release_soft_drop();
+        assert_eq!(game.grounded_at, Some(game.clock));
+        game.update(Duration::from_millis(50));
+        assert_eq!(game.stats.pieces, 0);
+        game.update(Duration::from_millis(60));
+        assert_eq!(game.stats.pieces, 1);
+    }
+
+    #[test]
+    fn lock_timeout_keeps_running_while_sliding() {
+        let mut game = game(&[".........."]);
+        game.config.lock_timeout = Duration::from_millis(100);
+        place(&mut game, TetrominoType::O, 4, ROWS as i32 - 2);
+        game.track_ground();
+        let grounded_at = game.grounded_at;
+        game.update(Duration::from_millis(60));
+        game.apply(Action::Left);
+        game.apply(Action::Rotate);
+        assert_eq!(game.grounded_at, grounded_at);
+        game.update(Duration::from_millis(60));
+        assert_eq!(game.stats.pieces, 1);
+    }
+
+    #[test]
+    fn lock_timeout_restarts_on_a_lower_row() {
+        let mut game = game(&["####......", "####......"]);
+        game.config.lock_timeout = Duration::from_millis(100);
+        place(&mut game, TetrominoType::O, 2, ROWS as i32 - 4);
+        game.lowest_y = game.current_y;
+        game.track_ground();
+        let grounded_at = game.grounded_at;
+        assert!(grounded_at.is_some());
+        game.update(Duration::from_millis(60));
+        game.apply(Action::Right);
+        game.apply(Action::Right);
+        assert_eq!(game.grounded_at, grounded_at);
+        assert!(game.move_piece(0, 1));
+        assert_eq!(game.grounded_at, None);
+        assert!(game.move_piece(0, 1));
+        assert_eq!(game.grounded_at, Some(game.clock));
+    }
+
+    #[test]
+    fn no_lock_timeout_leaves_gravity_timing_alone() {
+        let mut game = game(&[".........."]);
+        place(&mut game, TetrominoType::O, 4, ROWS as i32 - 2);
+        game.track_ground();
+        game.update(game.get_fall_speed() / 2);
+        assert_eq!(game.stats.pieces, 0);
+    }
+
+    #[test]
+    fn replays_keep_the_config_they_were_recorded_with() {
+        let dir = std::env::temp_dir().join(format!("tetris-tui-replay-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (config_path, board_path, replay_path) = (
+            dir.join("config.toml"),
+            dir.join("board.txt"),
+            dir.join("game.replay"),
+        );
+        fs::write(&config_path, "[spawn]\nchaos = true\n").unwrap();
+        fs::write(&board_path, "T...b.....\n####X#####\n").unwrap();
+        let args = vec![
+            "--config".to_string(),
+            config_path.display().to_string(),
+            "--board".to_string(),
+            board_path.display().to_string(),
+            "--goal".to_string(),
+            "40".to_string(),
+            "--serve".to_string(),
+            "7777".to_string(),
+        ];
+        let config = Config::from_args(args.iter().cloned()).unwrap();
+        Recorder::create(&replay_path, &args, &config, 7, false)
+            .unwrap()
+            .finish()
+            .unwrap();
+
+        fs::write(&config_path, "[spawn]\nchaos = false\n").unwrap();
+        fs::remove_file(&board_path).unwrap();
+        let replay = Replay::load(&replay_path).unwrap();
+        let source = replay.config.as_deref().unwrap();
+        let replayed = Config::from_replay(&replay.args, source, &replay.board).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(replayed.chaos);
+        assert_eq!(replayed.goal, Some(40));
+        assert_eq!(replayed.serve, None);
+        assert_eq!(replay.seed, 7);
+        assert!(replayed.start_board == config.start_board);
+    }
+
+    #[test]
+    fn serve_listens_locally_unless_given_a_host() {
+        let serve = |arg: &str| {
+            Config::from_args(["--serve".to_string(), arg.to_string()].into_iter())
+                .unwrap()
+                .serve
+                .unwrap()
+        };
+        assert_eq!(serve("7777"), ("127.0.0.1".to_string(), 7777));
+        assert_eq!(serve("0.0.0.0:7777"), ("0.0.0.0".to_string(), 7777));
+        assert_eq!(serve("[::]:7777"), ("::".to_string(), 7777));
+        assert_eq!(serve("[::1]:7777"), ("::1".to_string(), 7777));
+        for bad in ["host:x", "::1", "::1:7777", "[::1]", "[::1]7777"] {
+            let parsed = Config::from_args(["--serve".to_string(), bad.to_string()].into_iter());
+            assert!(parsed.is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn ascii_mode_covers_every_glyph() {
+        let sources = [
+            include_str!("main.rs"),
+            include_str!("ai.rs"),
+            include_str!("config.rs"),
+            include_str!("panel.rs"),
+            include_str!("replay.rs"),
+            include_str!("scores.rs"),
+            include_str!("screenshot.rs"),
+            include_str!("share.rs"),
+            include_str!("spectator.rs"),
+        ];
+        for c in sources.concat().chars().filter(|c| !c.is_ascii()) {
+            let unknown = "?".repeat(panel::char_width(c));
+            assert_ne!(ascii::convert(&c.to_string()), unknown, "{c}");
+        }
+        assert_eq!(ascii::convert("Record: à égalité"), "Record: a egalite");
+    }
+
+    #[test]
+    fn share_summary_names_the_mode_by_its_key() {
+        let mut game = game(&["#........."]);
+        for mode in [
+            GameMode::Classic,
+            GameMode::Cheese(5),
+            GameMode::PerfectClear,
+        ] {
+            game.config.mode = mode;
+            let summary = share::summary(&game);
+            let first = summary.lines().next().unwrap();
+            assert!(
+                first.starts_with(&format!("tetris-tui {} ", mode.key())),
+                "{first}"
+            );
+        }
+    }
+
+    #[test]
+    fn cell_width_counts_wide_glyphs() {
+        for (block, width) in [
+            ("██", 2),
+            ("#", 1),
+            ("[]", 2),
+            ("\u{1f7e5}", 2),
+            ("\u{53e3}", 2),
+            ("\u{53e3}\u{53e3}", 4),
+        ] {
+            let config = Config {
+                block: block.to_string(),
+                ..Config::default()
+            };
+            assert_eq!(config.cell_width(), width, "{block}");
+        }
+    }
+
+    #[test]
+    fn hammer_keys_reach_only_the_player_who_owns_them() {
+        set_muted(true);
+        let config = Config {
+            versus: true,
+            seed: Some(1),
+            ..Config::default()
+        };
+        let mut games = new_games(&config, false);
+        let players = players(&config);
+        games[1].hammers = 1;
+        let right = players[0].hammer[0];
+        let left = players[1].hammer[0];
+        assert!(key_inputs(&games, &players, right).is_empty());
+        let inputs = key_inputs(&games, &players, left);
+        assert!(matches!(inputs[..], [Input::ArmHammer(1)]));
+        apply_input(&mut games, inputs[0]);
+        assert!(games[1].hammer_mode && !games[0].hammer_mode);
+        assert!(matches!(
+            key_inputs(&games, &players, KeyCode::Char('1'))[..],
+            [Input::Hammer(1, 1)]
+        ));
+
+        for reserved in ["p", "g", "r", "q", "t", "v", "5"] {
+            let source = format!("[keys]\nrotate = \"{reserved}\"\n");
+            assert!(
+                Config::from_replay(&[], &source, &[]).is_err(),
+                "{reserved}"
+            );
+        }
+        let source = "[keys]\nhammer = \"f\"\n";
+        let config = Config::from_replay(&[], source, &[]).unwrap();
+        assert_eq!(config.keys.hammer, vec![KeyCode::Char('f')]);
+    }
+
+    #[test]
+    fn daily_replays_keep_the_date_they_were_played_on() {
+        let path =
+            std::env::temp_dir().join(format!("tetris-tui-daily-{}.replay", std::process::id()));
+        let args = vec![
+            "--daily".to_string(),
+            "--goal".to_string(),
+            "40".to_string(),
+        ];
+        let mut config = Config::from_args(args.iter().cloned()).unwrap();
+        config.daily = Some(20240101);
+        Recorder::create(&path, &args, &config, 20240101, false)
+            .unwrap()
+            .finish()
+            .unwrap();
+        let replay = Replay::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(replay.daily, Some(20240101));
+        assert_eq!(replay.seed, 20240101);
+        assert_eq!(replay.args, ["--goal", "40"]);
+    }
+
+    #[test]
+    fn panel_collapses_section_gaps_before_clipping() {
+        let text = |panel: &Panel| -> Vec<String> {
+            (0..panel.height())
+                .map(|row| panel.row(row).into_iter().map(|(_, text)| text).collect())
+                .collect()
+        };
+        let mut panel = Panel::new(20, 4);
+        panel.text(Color::White, "a");
+        panel.section("b", Color::White);
+        panel.text(Color::White, "c");
+        assert_eq!(text(&panel), ["a", "", "b", "c"]);
+        panel.section("d", Color::White);
+        assert_eq!(text(&panel), ["a", "b", "c", "d"]);
+        panel.text(Color::White, "e");
+        assert_eq!(text(&panel), ["a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn side_panel_keeps_the_controls_with_every_line_shown() {
+        let mut game = game(&[".........."]);
+        game.config = Config {
+            chaos: true,
+            goal: Some(40),
+            practice: true,
+            scenario: Some(1),
+            ..Config::default()
+        };
+        game.combo = 2;
+        game.ghost_mode = true;
+        game.slow_time_active = true;
+        game.hammers = 1;
+        game.tilt = 1;
+        game.well_until = Some(WELL_TIME);
+        game.reset_pending = true;
+        let panel = side_panel(&game, &game.config.keys);
+        let rows: Vec<String> = (0..panel.height())
+            .map(|row| panel.row(row).into_iter().map(|(_, text)| text).collect())
+            .collect();
+        assert_eq!(rows.len(), HEIGHT);
+        assert!(rows.iter().any(|row| row == "Contrôles:"));
+        assert!(rows.iter().any(|row| row.starts_with("R: vider")));
+        assert_eq!(rows[HEIGHT - 1], "1-7: Pièce  T: Scénario");
+    }
+
+    #[test]
+    fn slow_time_shows_its_multiplier_from_the_first_stack() {
+        let mut game = game(&[".........."]);
+        game.slow_time_active = true;
+        game.slow_time_end = Some(SLOW_TIME);
+        game.slow_time_stacks = 1;
+        let panel = side_panel(&game, &game.config.keys);
+        assert!((0..panel.height()).any(|row| {
+            panel
+                .row(row)
+                .iter()
+                .any(|(_, text)| text.starts_with("⏰ Slow Time x2 "))
+        }));
+    }
+
+    #[test]
+    fn controls_list_every_bound_key() {
+        assert_eq!(key_name(&[]), "-");
+        assert_eq!(key_name(&[KeyCode::Left, KeyCode::Char('a')]), "←/A");
+        let mut keys = KeyConfig::default();
+        keys.left.push(KeyCode::Char('a'));
+        let panel = side_panel(&game(&[".........."]), &keys);
+        assert!((0..panel.height()).any(|row| {
+            panel
+                .row(row)
+                .iter()
+                .any(|(_, text)| text.starts_with("←/A → ↑ ↓ Jouer"))
+        }));
+
+        let mut panel = Panel::new(8, 1);
+        panel.text(Color::White, "Space/Enter");
+        let mut out = Vec::new();
+        panel.draw(&mut out, 0).unwrap();
+        assert!(String::from_utf8(out).unwrap().ends_with("Space…"));
+    }
 }
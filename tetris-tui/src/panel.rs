@@ -0,0 +1,129 @@
+use crossterm::{
+    queue,
+    style::{Color, Print, SetForegroundColor},
+};
+use std::io::{self, Write};
+
+const INDENT: &str = "  ";
+
+pub type Segment = (Color, String);
+
+struct Line {
+    segments: Vec<Segment>,
+    spacer: bool,
+}
+
+pub struct Panel {
+    width: usize,
+    height: usize,
+    lines: Vec<Line>,
+}
+
+impl Panel {
+    pub fn new(width: usize, height: usize) -> Self {
+        Panel {
+            width,
+            height,
+            lines: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, segments: Vec<Segment>, spacer: bool) {
+        if self.lines.len() >= self.height {
+            let collapsible = (!spacer)
+                .then(|| self.lines.iter().rposition(|line| line.spacer))
+                .flatten();
+            let Some(at) = collapsible else {
+                return;
+            };
+            self.lines.remove(at);
+        }
+        self.lines.push(Line { segments, spacer });
+    }
+
+    pub fn line(&mut self, segments: Vec<Segment>) {
+        self.push(segments, false);
+    }
+
+    pub fn text(&mut self, color: Color, text: impl Into<String>) {
+        self.line(vec![(color, text.into())]);
+    }
+
+    pub fn blank(&mut self) {
+        self.line(Vec::new());
+    }
+
+    pub fn section(&mut self, title: &str, color: Color) {
+        if !self.lines.is_empty() {
+            self.push(Vec::new(), true);
+        }
+        self.text(color, title);
+    }
+
+    #[cfg(test)]
+    pub fn height(&self) -> usize {
+        self.lines.len()
+    }
+
+    #[cfg(test)]
+    pub fn row(&self, row: usize) -> Vec<Segment> {
+        self.lines[row].segments.clone()
+    }
+
+    pub fn draw(&self, stdout: &mut impl Write, row: usize) -> io::Result<()> {
+        let Some(Line { segments, .. }) = self.lines.get(row) else {
+            return Ok(());
+        };
+        if segments.is_empty() {
+            return Ok(());
+        }
+
+        let mut room = self.width.saturating_sub(INDENT.len());
+        let overflows = segments
+            .iter()
+            .flat_map(|(_, text)| text.chars())
+            .map(char_width)
+            .sum::<usize>()
+            > room;
+        if overflows {
+            room = room.saturating_sub(1);
+        }
+        queue!(stdout, Print(INDENT))?;
+        for (color, text) in segments {
+            let mut clipped = String::new();
+            for c in text.chars() {
+                let width = char_width(c);
+                if width > room {
+                    room = 0;
+                    break;
+                }
+                room -= width;
+                clipped.push(c);
+            }
+            queue!(stdout, SetForegroundColor(*color), Print(clipped))?;
+            if room == 0 {
+                break;
+            }
+        }
+        if overflows {
+            queue!(stdout, Print("…"))?;
+        }
+        Ok(())
+    }
+}
+
+pub fn char_width(c: char) -> usize {
+    match c {
+        '\u{1100}'..='\u{115f}'
+        | '\u{23e9}'..='\u{23fa}'
+        | '\u{2600}'..='\u{27bf}'
+        | '\u{2e80}'..='\u{a4cf}'
+        | '\u{ac00}'..='\u{d7a3}'
+        | '\u{f900}'..='\u{faff}'
+        | '\u{fe30}'..='\u{fe4f}'
+        | '\u{ff00}'..='\u{ff60}'
+        | '\u{ffe0}'..='\u{ffe6}'
+        | '\u{1f300}'.. => 2,
+        _ => 1,
+    }
+}
@@ -0,0 +1,247 @@
+use crate::{
+    Action, TetrominoType,
+    config::{self, Config},
+};
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::Path,
+    time::Duration,
+};
+
+const HEADER: &str = "tetris-tui replay 2";
+const LEGACY_HEADER: &str = "tetris-tui replay 1";
+
+#[derive(Clone, Copy)]
+pub enum Input {
+    Action(usize, Action),
+    Release(usize),
+    Pause(bool),
+    Grid,
+    Reset,
+    Hammer(usize, u32),
+    CancelHammer(usize),
+    ArmHammer(usize),
+    HoldPreview(bool),
+    Peek(usize, bool),
+    Charge(usize, i32),
+    FallMs(u64),
+    HoldRules(bool, bool),
+    Force(TetrominoType),
+    Restart,
+    Scenario,
+}
+
+pub enum Entry {
+    Tick(Duration),
+    Input(Input),
+}
+
+pub struct Recorder {
+    out: BufWriter<File>,
+}
+
+pub struct Replay {
+    pub args: Vec<String>,
+    pub config: Option<String>,
+    pub board: Vec<String>,
+    pub seed: u64,
+    pub daily: Option<u32>,
+    pub key_releases: bool,
+    pub entries: Vec<Entry>,
+}
+
+const ACTIONS: [(Action, &str); 6] = [
+    (Action::Left, "left"),
+    (Action::Right, "right"),
+    (Action::SoftDrop, "soft_drop"),
+    (Action::Rotate, "rotate"),
+    (Action::HardDrop, "hard_drop"),
+    (Action::Hold, "hold"),
+];
+
+impl Recorder {
+    pub fn create(
+        path: &Path,
+        args: &[String],
+        config: &Config,
+        seed: u64,
+        key_releases: bool,
+    ) -> io::Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "{HEADER}")?;
+        writeln!(out, "seed {seed}")?;
+        writeln!(out, "releases {}", u8::from(key_releases))?;
+        if let Some(date) = config.daily {
+            writeln!(out, "daily {date}")?;
+        }
+        for line in config.source.lines() {
+            writeln!(out, "config {line}")?;
+        }
+        for row in config.start_board.iter().flatten() {
+            let row: String = row.iter().map(|&cell| config::board_char(cell)).collect();
+            writeln!(out, "board {row}")?;
+        }
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if matches!(
+                arg.as_str(),
+                "--record" | "--config" | "--board" | "--serve"
+            ) {
+                args.next();
+            } else if arg != "--daily" {
+                writeln!(out, "arg {arg}")?;
+            }
+        }
+        Ok(Recorder { out })
+    }
+
+    pub fn tick(&mut self, dt: Duration) -> io::Result<()> {
+        writeln!(self.out, "t {}", dt.as_nanos())
+    }
+
+    pub fn input(&mut self, input: Input) -> io::Result<()> {
+        match input {
+            Input::Action(player, action) => {
+                let name = ACTIONS
+                    .iter()
+                    .find(|(a, _)| *a == action)
+                    .map_or("", |(_, name)| name);
+                writeln!(self.out, "a {player} {name}")
+            }
+            Input::Release(player) => writeln!(self.out, "u {player}"),
+            Input::Pause(paused) => writeln!(self.out, "p {}", u8::from(paused)),
+            Input::Grid => writeln!(self.out, "g"),
+            Input::Reset => writeln!(self.out, "r"),
+            Input::Hammer(player, digit) => writeln!(self.out, "h {player} {digit}"),
+            Input::CancelHammer(player) => writeln!(self.out, "e {player}"),
+            Input::ArmHammer(player) => writeln!(self.out, "m {player}"),
+            Input::HoldPreview(on) => writeln!(self.out, "v {}", u8::from(on)),
+            Input::Peek(player, on) => writeln!(self.out, "k {player} {}", u8::from(on)),
+            Input::Charge(player, dir) => writeln!(self.out, "c {player} {dir}"),
+            Input::FallMs(ms) => writeln!(self.out, "d {ms}"),
+            Input::HoldRules(first, next_tick) => {
+                writeln!(self.out, "o {} {}", u8::from(first), u8::from(next_tick))
+            }
+            Input::Force(typ) => writeln!(self.out, "f {}", typ.name()),
+            Input::Restart => writeln!(self.out, "n"),
+            Input::Scenario => writeln!(self.out, "s"),
+        }
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+impl Replay {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|err| format!("{}: {err}", path.display()))?;
+        let mut lines = text.lines().enumerate();
+        let config = match lines.next().map(|(_, line)| line) {
+            Some(HEADER) => Some(String::new()),
+            Some(LEGACY_HEADER) => None,
+            _ => return Err(format!("{}: not a tetris-tui replay", path.display())),
+        };
+
+        let mut replay = Replay {
+            args: Vec::new(),
+            config,
+            board: Vec::new(),
+            seed: 0,
+            daily: None,
+            key_releases: false,
+            entries: Vec::new(),
+        };
+        for (n, line) in lines {
+            let at = || format!("{}:{}: invalid line: {line}", path.display(), n + 1);
+            let (kind, rest) = line.split_once(' ').unwrap_or((line, ""));
+            let number = || rest.parse::<u64>().map_err(|_| at());
+            match kind {
+                "seed" => replay.seed = number()?,
+                "releases" => replay.key_releases = number()? == 1,
+                "daily" => replay.daily = Some(rest.parse().map_err(|_| at())?),
+                "arg" => replay.args.push(rest.to_string()),
+                "config" => {
+                    let source = replay.config.as_mut().ok_or_else(at)?;
+                    source.push_str(rest);
+                    source.push('\n');
+                }
+                "board" => replay.board.push(rest.to_string()),
+                "t" => replay
+                    .entries
+                    .push(Entry::Tick(Duration::from_nanos(number()?))),
+                _ => {
+                    let input = parse_input(kind, rest).ok_or_else(at)?;
+                    replay.entries.push(Entry::Input(input));
+                }
+            }
+        }
+        Ok(replay)
+    }
+}
+
+fn hammer_player(rest: &str) -> Option<usize> {
+    if rest.is_empty() {
+        Some(0)
+    } else {
+        rest.parse().ok()
+    }
+}
+
+fn parse_input(kind: &str, rest: &str) -> Option<Input> {
+    match kind {
+        "a" => {
+            let (player, name) = rest.split_once(' ')?;
+            let action = ACTIONS.iter().find(|(_, n)| *n == name)?.0;
+            Some(Input::Action(player.parse().ok()?, action))
+        }
+        "u" => Some(Input::Release(rest.parse().ok()?)),
+        "p" => Some(Input::Pause(rest == "1")),
+        "g" => Some(Input::Grid),
+        "r" => Some(Input::Reset),
+        "n" => Some(Input::Restart),
+        "s" => Some(Input::Scenario),
+        "e" => hammer_player(rest).map(Input::CancelHammer),
+        "m" => hammer_player(rest).map(Input::ArmHammer),
+        "v" => Some(Input::HoldPreview(rest == "1")),
+        "d" => rest.parse().ok().map(Input::FallMs),
+        "o" => {
+            let (first, next_tick) = rest.split_once(' ')?;
+            Some(Input::HoldRules(first == "1", next_tick == "1"))
+        }
+        "k" => {
+            let (player, on) = rest.split_once(' ')?;
+            Some(Input::Peek(player.parse().ok()?, on == "1"))
+        }
+        "c" => {
+            let (player, dir) = rest.split_once(' ')?;
+            Some(Input::Charge(player.parse().ok()?, dir.parse().ok()?))
+        }
+        "h" => match rest.split_once(' ') {
+            Some((player, digit)) => Some(Input::Hammer(player.parse().ok()?, digit.parse().ok()?)),
+            None => Some(Input::Hammer(0, rest.parse().ok()?)),
+        },
+        "f" => {
+            let name = rest.chars().next()?;
+            TetrominoType::ALL
+                .into_iter()
+                .find(|typ| typ.name() == name)
+                .map(Input::Force)
+        }
+        _ => None,
+    }
+}
+
+pub fn cast_line(time: Duration, frame: &[u8]) -> String {
+    let mut text = String::new();
+    for c in String::from_utf8_lossy(frame).chars() {
+        match c {
+            '"' => text.push_str("\\\""),
+            '\\' => text.push_str("\\\\"),
+            c if (c as u32) < 0x20 => text.push_str(&format!("\\u{:04x}", c as u32)),
+            c => text.push(c),
+        }
+    }
+    format!("[{:.6}, \"o\", \"{text}\"]", time.as_secs_f64())
+}
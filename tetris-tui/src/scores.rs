@@ -0,0 +1,92 @@
+use crate::config;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+#[derive(Clone)]
+pub struct Pace {
+    pub time: Duration,
+    pub lines: Vec<u32>,
+}
+
+impl Pace {
+    pub fn lines_at(&self, elapsed: Duration) -> u32 {
+        let second = elapsed.as_secs() as usize;
+        self.lines
+            .get(second)
+            .or(self.lines.last())
+            .copied()
+            .unwrap_or(0)
+    }
+}
+
+pub fn path(config_path: &Path) -> PathBuf {
+    config_path.with_file_name("scores.toml")
+}
+
+pub fn load_best(path: &Path, mode: &str) -> u32 {
+    entries(path, "best")
+        .into_iter()
+        .find(|(key, _)| key == mode)
+        .or_else(|| legacy_best(path).filter(|_| mode == "classic"))
+        .and_then(|(_, value)| value.parse().ok())
+        .unwrap_or(0)
+}
+
+pub fn load_top(path: &Path) -> u32 {
+    entries(path, "best")
+        .into_iter()
+        .chain(legacy_best(path))
+        .filter_map(|(_, value)| value.parse().ok())
+        .max()
+        .unwrap_or(0)
+}
+
+pub fn save_best(path: &Path, mode: &str, score: u32) -> io::Result<()> {
+    config::save(path, &[(&format!("best.{mode}"), score.to_string())])
+}
+
+fn legacy_best(path: &Path) -> Option<(String, String)> {
+    entries(path, "").into_iter().find(|(key, _)| key == "best")
+}
+
+pub fn load_pace(path: &Path, mode: &str) -> Option<Pace> {
+    let (_, value) = entries(path, "pace")
+        .into_iter()
+        .find(|(key, _)| key == mode)?;
+    let (ms, lines) = value.trim_matches('"').split_once(';')?;
+    Some(Pace {
+        time: Duration::from_millis(ms.parse().ok()?),
+        lines: lines
+            .split(',')
+            .filter(|n| !n.is_empty())
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .ok()?,
+    })
+}
+
+pub fn save_pace(path: &Path, mode: &str, pace: &Pace) -> io::Result<()> {
+    let lines: Vec<String> = pace.lines.iter().map(u32::to_string).collect();
+    let value = format!("\"{};{}\"", pace.time.as_millis(), lines.join(","));
+    config::save(path, &[(&format!("pace.{mode}"), value)])
+}
+
+fn entries(path: &Path, section: &str) -> Vec<(String, String)> {
+    let text = fs::read_to_string(path).unwrap_or_default();
+    let mut current = "";
+    let mut found = Vec::new();
+    for line in text.lines() {
+        let line = line.trim();
+        if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            current = header.trim();
+        } else if current == section
+            && let Some((key, value)) = line.split_once('=')
+        {
+            found.push((key.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    found
+}
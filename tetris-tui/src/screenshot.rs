@@ -0,0 +1,83 @@
+use crate::panel::char_width;
+use std::{
+    fs, io,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const WIDE: char = '\0';
+
+pub fn save(frame: &[u8]) -> io::Result<PathBuf> {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let path = PathBuf::from(format!("tetris-{secs}.txt"));
+    fs::write(&path, plain_text(frame))?;
+    Ok(path)
+}
+
+fn plain_text(frame: &[u8]) -> String {
+    let text = String::from_utf8_lossy(frame);
+    let mut screen: Vec<Vec<char>> = Vec::new();
+    let (mut row, mut col) = (0, 0);
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => {
+                if chars.next() != Some('[') {
+                    continue;
+                }
+                let mut params = String::new();
+                let command = loop {
+                    match chars.next() {
+                        Some(c @ '\x40'..='\x7e') => break Some(c),
+                        Some(c) => params.push(c),
+                        None => break None,
+                    }
+                };
+                let numbers: Vec<usize> =
+                    params.split(';').map(|n| n.parse().unwrap_or(1)).collect();
+                match command {
+                    Some('H') => {
+                        row = numbers.first().copied().unwrap_or(1).max(1) - 1;
+                        col = numbers.get(1).copied().unwrap_or(1).max(1) - 1;
+                    }
+                    Some('K') if params.is_empty() || params == "0" => {
+                        if let Some(line) = screen.get_mut(row) {
+                            line.truncate(col);
+                        }
+                    }
+                    Some('J') if params == "2" => screen.clear(),
+                    _ => {}
+                }
+            }
+            '\r' => col = 0,
+            '\n' => row += 1,
+            c if c < ' ' => {}
+            c => {
+                if screen.len() <= row {
+                    screen.resize(row + 1, Vec::new());
+                }
+                let line = &mut screen[row];
+                let width = char_width(c);
+                if line.len() < col + width {
+                    line.resize(col + width, ' ');
+                }
+                line[col] = c;
+                if width == 2 {
+                    line[col + 1] = WIDE;
+                }
+                col += width;
+            }
+        }
+    }
+
+    let mut out = String::new();
+    for line in screen {
+        let line: String = line.into_iter().filter(|&c| c != WIDE).collect();
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out
+}
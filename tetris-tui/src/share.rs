@@ -0,0 +1,71 @@
+use crate::{BUFFER_ROWS, CellType, Color, Game};
+use std::io::{self, Write};
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn summary(game: &Game) -> String {
+    let mode = game.config.mode.key();
+    let secs = game.clock.as_secs();
+    let seed = match game.config.daily {
+        Some(date) => format!("daily {date}"),
+        None => format!("seed {}", game.seed),
+    };
+
+    let mut text = format!(
+        "tetris-tui {mode} {}\nScore {} | Lignes {} | {}:{:02} | {seed}\n",
+        if game.won { "🧀" } else { "💀" },
+        game.score,
+        game.lines_cleared_total,
+        secs / 60,
+        secs % 60
+    );
+    for row in game
+        .board
+        .iter()
+        .skip(BUFFER_ROWS)
+        .skip_while(|row| row.iter().all(Option::is_none))
+    {
+        text.extend(row.iter().map(|cell| emoji(*cell)));
+        text.push('\n');
+    }
+    text
+}
+
+fn emoji(cell: Option<CellType>) -> char {
+    match cell {
+        None => '⬛',
+        Some(CellType::Normal(color, _)) => match color {
+            Color::Red => '🟥',
+            Color::Green => '🟩',
+            Color::Yellow => '🟨',
+            Color::Blue | Color::Cyan => '🟦',
+            Color::Magenta => '🟪',
+            _ => '🟧',
+        },
+        Some(CellType::Garbage | CellType::Obstacle) => '⬜',
+        Some(CellType::PowerUp(_)) => '🟫',
+    }
+}
+
+pub fn copy(out: &mut impl Write, text: &str) -> io::Result<()> {
+    write!(out, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+    out.flush()
+}
+
+fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
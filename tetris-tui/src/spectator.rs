@@ -0,0 +1,139 @@
+use crate::{BUFFER_ROWS, CellType, Game, PowerUpType};
+use std::{
+    io::{self, ErrorKind, Write},
+    net::{TcpListener, TcpStream},
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
+
+pub struct Spectator {
+    sender: mpsc::Sender<String>,
+    last: String,
+}
+
+struct Client {
+    stream: TcpStream,
+    pending: Vec<u8>,
+}
+
+impl Spectator {
+    pub fn bind(host: &str, port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind((host, port))?;
+        listener.set_nonblocking(true)?;
+
+        let (sender, receiver) = mpsc::channel::<String>();
+        thread::spawn(move || {
+            let mut clients: Vec<Client> = Vec::new();
+            let mut latest = String::new();
+
+            loop {
+                let fresh = match receiver.recv_timeout(Duration::from_millis(100)) {
+                    Ok(snapshot) => {
+                        latest = receiver.try_iter().last().unwrap_or(snapshot);
+                        true
+                    }
+                    Err(RecvTimeoutError::Timeout) => false,
+                    Err(RecvTimeoutError::Disconnected) => break,
+                };
+
+                while let Ok((stream, _)) = listener.accept() {
+                    if stream.set_nonblocking(true).is_ok() {
+                        clients.push(Client {
+                            stream,
+                            pending: latest.as_bytes().to_vec(),
+                        });
+                    }
+                }
+
+                clients.retain_mut(|client| {
+                    if fresh && client.pending.is_empty() {
+                        client.pending = latest.as_bytes().to_vec();
+                    }
+                    client.flush()
+                });
+            }
+        });
+
+        Ok(Spectator {
+            sender,
+            last: String::new(),
+        })
+    }
+
+    pub fn send(&mut self, game: &Game) {
+        let snapshot = snapshot(game);
+        if snapshot != self.last {
+            let _ = self.sender.send(format!("{snapshot}\n"));
+            self.last = snapshot;
+        }
+    }
+}
+
+impl Client {
+    fn flush(&mut self) -> bool {
+        while !self.pending.is_empty() {
+            match self.stream.write(&self.pending) {
+                Ok(0) => return false,
+                Ok(n) => {
+                    self.pending.drain(..n);
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return true,
+                Err(_) => return false,
+            }
+        }
+        true
+    }
+}
+
+pub fn snapshot(game: &Game) -> String {
+    let rows: Vec<String> = game
+        .board
+        .iter()
+        .skip(BUFFER_ROWS)
+        .map(|row| format!("\"{}\"", board_row(row)))
+        .collect();
+
+    let hold = match game.hold {
+        Some(typ) => format!("\"{}\"", typ.name()),
+        None => "null".to_string(),
+    };
+
+    format!(
+        "{{\"score\":{},\"lines\":{},\"combo\":{},\"game_over\":{},\"paused\":{},\
+         \"current\":{{\"type\":\"{}\",\"x\":{},\"y\":{},\"rotation\":{}}},\
+         \"next\":\"{}\",\"hold\":{},\"hash\":\"{:016x}\",\"board\":[{}]}}",
+        game.score,
+        game.lines_cleared_total,
+        game.combo,
+        game.game_over,
+        game.paused,
+        game.current.typ.name(),
+        game.current_x,
+        game.current_y - BUFFER_ROWS as i32,
+        game.current.rotation,
+        game.next[0].typ.name(),
+        hold,
+        game.state_hash(),
+        rows.join(",")
+    )
+}
+
+pub fn board_row(row: &[Option<CellType>]) -> String {
+    row.iter()
+        .map(|cell| match cell {
+            None => '.',
+            Some(CellType::Normal(..) | CellType::Garbage) => '#',
+            Some(CellType::Obstacle) => 'X',
+            Some(CellType::PowerUp(powerup)) => match powerup {
+                PowerUpType::Bomb => 'b',
+                PowerUpType::SlowTime => 's',
+                PowerUpType::Ghost => 'g',
+                PowerUpType::Hammer => 'h',
+                PowerUpType::Random => 'r',
+                PowerUpType::Well => 'w',
+                PowerUpType::Tilt => 'm',
+            },
+        })
+        .collect()
+}